@@ -1,32 +1,53 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
-use rand::{Rng, thread_rng};
-use coup_rs::Coup;
+use rand::thread_rng;
+use coup_rs::{CompactCoup, Coup, GameConfig};
 
-fn complete_game(num_players: u8) {
+fn complete_game(config: GameConfig) {
     let mut rng = thread_rng();
-    let mut coup = black_box(Coup::new(num_players, &mut rng));
+    let mut coup = black_box(Coup::with_config(config, &mut rng).unwrap());
     for _ in 0..1000 {
-        let mut actions = coup.actions();
+        let random_action = coup.random_action(&mut rng).unwrap();
 
-        let random_index = rng.gen_range(0..actions.len());
-        let random_action = actions.remove(random_index);
+        coup = coup.apply_action(random_action, &mut rng).unwrap();
+
+        if coup.winner().is_some() {
+            break;
+        }
+    }
+}
 
+// same game played on the packed representation - a fraction of the size to store and hash, for
+// the cost of unpacking it to make each move
+fn complete_compact_game(config: GameConfig) {
+    let mut rng = thread_rng();
+    let mut coup = black_box(CompactCoup::from(&Coup::with_config(config, &mut rng).unwrap()));
+    for _ in 0..1000 {
+        let random_action = coup.random_action(&mut rng).unwrap();
         coup = coup.apply_action(random_action, &mut rng).unwrap();
 
-        if let Some(_) = coup.winner() {
+        if coup.winner().is_some() {
             break;
         }
     }
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
+    // both representations in the one group, so they're reported side by side
     let mut group = c.benchmark_group("complete_game");
-    for num_players in 3..=6u8 {
-        group.bench_with_input(BenchmarkId::from_parameter(num_players), &num_players, |b, &num_players| {
-            b.iter(|| complete_game(num_players))
+    for num_players in 3..=8u8 {
+        group.bench_with_input(BenchmarkId::new("coup", num_players), &num_players, |b, &num_players| {
+            b.iter(|| complete_game(GameConfig::official(num_players)))
+        });
+        group.bench_with_input(BenchmarkId::new("compact", num_players), &num_players, |b, &num_players| {
+            b.iter(|| complete_compact_game(GameConfig::official(num_players)))
         });
     }
+
+    group.bench_function("two player", |b| {
+        b.iter(|| complete_game(GameConfig::two_player()))
+    });
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
-criterion_main!(benches);
\ No newline at end of file
+criterion_main!(benches);
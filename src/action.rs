@@ -2,32 +2,117 @@ use std::fmt::{Debug, Formatter};
 use serde::{Deserialize, Serialize};
 use crate::{Character};
 
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+// proposals (foreign aid, tax, assassinate, steal, exchange, embezzle and examine) are taken as-is while
+// awaiting a proposal, and go on to be blocked, challenged or resolved
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "StoredAction")]
 pub enum Action {
-    Propose(usize, Box<Action>),
     Income(usize),
     ForeignAid(usize),
     Tax(usize),
     Assassinate(usize, usize),
     Coup(usize, usize),
     Steal(usize, usize),
-    Exchange(usize, usize),
+    Exchange(usize),
     Block(usize, Character),
     Relent(usize),
     Challenge(usize),
     Lose(usize, usize), // index of card revealed & lost, and if to end the turn after the loss
     Reveal(usize, usize), // index of card exchanged
     Pass(usize),
-    Resolve(usize)
+    Resolve(usize),
+    Keep(usize, Character, Option<Character>), // the cards an exchanger keeps, one per live influence
+    ChooseInfluence(usize, Character), // starting influence picked in a two player game
+    Convert(usize, usize), // change the faction of yourself or another player
+    Embezzle(usize), // take the treasury reserve, claiming to not have a duke
+    Disprove(usize), // show a hand without a duke to win an embezzle challenge
+    Examine(usize, usize), // inquisitor looks at one of the target's cards
+    Show(usize, usize), // index of the card the examined player shows the inquisitor
+    ForceSwap(usize), // inquisitor makes the examined player exchange the shown card
+    Release(usize), // inquisitor lets the examined player keep the shown card
+    Leave(usize, Departure), // the player's whole hand is revealed and they're out, whenever they like
+}
+
+// why a player left before being knocked out
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Departure {
+    Concede,
+    Timeout,
+    // taken out by the table or the server, like for dropping their connection
+    Removed,
+}
+
+impl Action {
+    // the player taking the action
+    pub fn player_idx(&self) -> usize {
+        match *self {
+            Action::Income(player_idx) |
+            Action::ForeignAid(player_idx) |
+            Action::Tax(player_idx) |
+            Action::Assassinate(player_idx, _) |
+            Action::Coup(player_idx, _) |
+            Action::Steal(player_idx, _) |
+            Action::Exchange(player_idx) |
+            Action::Block(player_idx, _) |
+            Action::Relent(player_idx) |
+            Action::Challenge(player_idx) |
+            Action::Lose(player_idx, _) |
+            Action::Reveal(player_idx, _) |
+            Action::Pass(player_idx) |
+            Action::Resolve(player_idx) |
+            Action::Keep(player_idx, _, _) |
+            Action::ChooseInfluence(player_idx, _) |
+            Action::Convert(player_idx, _) |
+            Action::Embezzle(player_idx) |
+            Action::Disprove(player_idx) |
+            Action::Examine(player_idx, _) |
+            Action::Show(player_idx, _) |
+            Action::ForceSwap(player_idx) |
+            Action::Release(player_idx) |
+            Action::Leave(player_idx, _) => player_idx,
+        }
+    }
+
+    // the player on the receiving end of the action, if there is one
+    pub fn target_player_idx(&self) -> Option<usize> {
+        match *self {
+            Action::Assassinate(_, target_player_idx) |
+            Action::Coup(_, target_player_idx) |
+            Action::Steal(_, target_player_idx) |
+            Action::Convert(_, target_player_idx) |
+            Action::Examine(_, target_player_idx) => Some(target_player_idx),
+            _ => None,
+        }
+    }
+
+    // the same action written the way Coup::actions() lists it - the cards kept by an exchange are
+    // one choice whichever order they're given in
+    pub fn normalized(&self) -> Action {
+        match *self {
+            Action::Keep(player_idx, first, Some(second)) if (second as usize) < (first as usize) => Action::Keep(player_idx, second, Some(first)),
+            action => action,
+        }
+    }
+
+    // actions that can be blocked or challenged before they resolve
+    pub fn is_proposal(&self) -> bool {
+        matches!(
+            self,
+            Action::ForeignAid(_) |
+            Action::Tax(_) |
+            Action::Assassinate(_, _) |
+            Action::Steal(_, _) |
+            Action::Exchange(_) |
+            Action::Embezzle(_) |
+            Action::Examine(_, _)
+        )
+    }
 }
 
 impl Debug for Action {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 
         match self {
-            Action::Propose(player_idx, proposal) => {
-                f.write_fmt(format_args!("Player {player_idx} proposes \"{:?}\"", proposal))
-            },
             Action::Income(player_idx) => {
                 f.write_fmt(format_args!("Player {player_idx} takes Income"))
             }
@@ -46,8 +131,8 @@ impl Debug for Action {
             Action::Steal(player_idx, target_player_idx) => {
                 f.write_fmt(format_args!("Player {player_idx} steals from {target_player_idx}"))
             }
-            Action::Exchange(player_idx, card_idx) => {
-                f.write_fmt(format_args!("Player {player_idx} exchanges card {card_idx}"))
+            Action::Exchange(player_idx) => {
+                f.write_fmt(format_args!("Player {player_idx} exchanges with the deck"))
             }
             Action::Block(player_idx, character) => {
                 f.write_fmt(format_args!("Player {player_idx} blocks with {:?}", character))
@@ -70,6 +155,181 @@ impl Debug for Action {
             Action::Resolve(player_idx) => {
                 f.write_fmt(format_args!("Player {player_idx} resolves their proposal"))
             }
+            Action::Keep(player_idx, first, None) => {
+                f.write_fmt(format_args!("Player {player_idx} keeps {:?}", first))
+            }
+            Action::Keep(player_idx, first, Some(second)) => {
+                f.write_fmt(format_args!("Player {player_idx} keeps {:?} and {:?}", first, second))
+            }
+            Action::ChooseInfluence(player_idx, character) => {
+                f.write_fmt(format_args!("Player {player_idx} chooses {:?} as starting influence", character))
+            }
+            Action::Convert(player_idx, target_player_idx) => {
+                f.write_fmt(format_args!("Player {player_idx} converts {target_player_idx}"))
+            }
+            Action::Embezzle(player_idx) => {
+                f.write_fmt(format_args!("Player {player_idx} embezzles the treasury reserve"))
+            }
+            Action::Disprove(player_idx) => {
+                f.write_fmt(format_args!("Player {player_idx} shows they have no Duke"))
+            }
+            Action::Examine(player_idx, target_player_idx) => {
+                f.write_fmt(format_args!("Player {player_idx} examines {target_player_idx}"))
+            }
+            Action::Show(player_idx, card_idx) => {
+                f.write_fmt(format_args!("Player {player_idx} shows card {card_idx}"))
+            }
+            Action::ForceSwap(player_idx) => {
+                f.write_fmt(format_args!("Player {player_idx} forces the shown card to be exchanged"))
+            }
+            Action::Release(player_idx) => {
+                f.write_fmt(format_args!("Player {player_idx} lets the shown card be kept"))
+            }
+            Action::Leave(player_idx, departure) => {
+                f.write_fmt(format_args!("Player {player_idx} leaves the game ({:?})", departure))
+            }
+        }
+    }
+}
+
+// how actions used to be stored, with proposals wrapped in Propose - old logs still deserialize
+// into Action through this, and serializing them again writes the current format
+#[derive(Deserialize)]
+enum StoredAction {
+    // the proposer is repeated inside the proposal
+    #[allow(dead_code)]
+    Propose(usize, Box<StoredAction>),
+    Income(usize),
+    ForeignAid(usize),
+    Tax(usize),
+    Assassinate(usize, usize),
+    Coup(usize, usize),
+    Steal(usize, usize),
+    Exchange(StoredExchange),
+    Block(usize, Character),
+    Relent(usize),
+    Challenge(usize),
+    Lose(usize, usize),
+    Reveal(usize, usize),
+    Pass(usize),
+    Resolve(usize),
+    Keep(usize, Character, Option<Character>),
+    ChooseInfluence(usize, Character),
+    Convert(usize, usize),
+    Embezzle(usize),
+    Disprove(usize),
+    Examine(usize, usize),
+    Show(usize, usize),
+    ForceSwap(usize),
+    Release(usize),
+    Leave(usize, Departure),
+}
+
+// exchanges used to name the card being swapped out, now the whole hand is up for exchange
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredExchange {
+    Player(usize),
+    #[allow(dead_code)]
+    WithCard(usize, usize),
+}
+
+impl From<StoredAction> for Action {
+    fn from(action: StoredAction) -> Self {
+        match action {
+            StoredAction::Propose(_, proposal) => Action::from(*proposal),
+            StoredAction::Income(player_idx) => Action::Income(player_idx),
+            StoredAction::ForeignAid(player_idx) => Action::ForeignAid(player_idx),
+            StoredAction::Tax(player_idx) => Action::Tax(player_idx),
+            StoredAction::Assassinate(player_idx, target_player_idx) => Action::Assassinate(player_idx, target_player_idx),
+            StoredAction::Coup(player_idx, target_player_idx) => Action::Coup(player_idx, target_player_idx),
+            StoredAction::Steal(player_idx, target_player_idx) => Action::Steal(player_idx, target_player_idx),
+            StoredAction::Exchange(StoredExchange::Player(player_idx) | StoredExchange::WithCard(player_idx, _)) => Action::Exchange(player_idx),
+            StoredAction::Block(player_idx, character) => Action::Block(player_idx, character),
+            StoredAction::Relent(player_idx) => Action::Relent(player_idx),
+            StoredAction::Challenge(player_idx) => Action::Challenge(player_idx),
+            StoredAction::Lose(player_idx, card_idx) => Action::Lose(player_idx, card_idx),
+            StoredAction::Reveal(player_idx, card_idx) => Action::Reveal(player_idx, card_idx),
+            StoredAction::Pass(player_idx) => Action::Pass(player_idx),
+            StoredAction::Resolve(player_idx) => Action::Resolve(player_idx),
+            StoredAction::Keep(player_idx, first, second) => Action::Keep(player_idx, first, second),
+            StoredAction::ChooseInfluence(player_idx, character) => Action::ChooseInfluence(player_idx, character),
+            StoredAction::Convert(player_idx, target_player_idx) => Action::Convert(player_idx, target_player_idx),
+            StoredAction::Embezzle(player_idx) => Action::Embezzle(player_idx),
+            StoredAction::Disprove(player_idx) => Action::Disprove(player_idx),
+            StoredAction::Examine(player_idx, target_player_idx) => Action::Examine(player_idx, target_player_idx),
+            StoredAction::Show(player_idx, card_idx) => Action::Show(player_idx, card_idx),
+            StoredAction::ForceSwap(player_idx) => Action::ForceSwap(player_idx),
+            StoredAction::Release(player_idx) => Action::Release(player_idx),
+            StoredAction::Leave(player_idx, departure) => Action::Leave(player_idx, departure),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Action;
+    use crate::Character::{Ambassador, Captain, Duke};
+
+    #[test]
+    fn serde_format() {
+        let actions = [Action::Tax(0), Action::Steal(1, 2), Action::Keep(0, Duke, Some(Captain)), Action::Keep(1, Captain, None)];
+
+        for action in actions {
+            let json = serde_json::to_string(&action).unwrap();
+            assert_eq!(serde_json::from_str::<Action>(&json).unwrap(), action);
+        }
+
+        assert_eq!(serde_json::to_string(&Action::Steal(1, 2)).unwrap(), r#"{"Steal":[1,2]}"#);
+    }
+
+    #[test]
+    fn loads_wrapped_proposals() {
+        let log = r#"[{"Propose":[0,{"Tax":0}]},{"Challenge":1},{"Propose":[1,{"Assassinate":[1,0]}]},"Income"]"#;
+        assert!(serde_json::from_str::<Vec<Action>>(log).is_err());
+
+        let log = r#"[{"Propose":[0,{"Tax":0}]},{"Challenge":1},{"Propose":[1,{"Assassinate":[1,0]}]},{"Income":2}]"#;
+        let actions: Vec<Action> = serde_json::from_str(log).unwrap();
+        assert_eq!(actions, vec![Action::Tax(0), Action::Challenge(1), Action::Assassinate(1, 0), Action::Income(2)]);
+
+        // written back out in the flat format
+        assert_eq!(serde_json::to_string(&actions).unwrap(), r#"[{"Tax":0},{"Challenge":1},{"Assassinate":[1,0]},{"Income":2}]"#);
+    }
+
+    #[test]
+    fn loads_baseline_logs() {
+        // a log written before exchanges drew two, when they named the card being swapped out
+        let log = r#"[
+            {"Income":0},
+            {"Propose":[1,{"Exchange":[1,0]}]},
+            {"Pass":2},
+            {"Pass":0},
+            {"Resolve":1},
+            {"Propose":[2,{"Steal":[2,0]}]},
+            {"Block":[0,"Ambassador"]},
+            {"Challenge":2},
+            {"Reveal":[0,1]},
+            {"Lose":[2,0]},
+            {"Exchange":[0,1]},
+            {"Coup":[1,2]}
+        ]"#;
+
+        let actions: Vec<Action> = serde_json::from_str(log).unwrap();
+        assert_eq!(actions, vec![
+            Action::Income(0),
+            Action::Exchange(1),
+            Action::Pass(2),
+            Action::Pass(0),
+            Action::Resolve(1),
+            Action::Steal(2, 0),
+            Action::Block(0, Ambassador),
+            Action::Challenge(2),
+            Action::Reveal(0, 1),
+            Action::Lose(2, 0),
+            Action::Exchange(0),
+            Action::Coup(1, 2),
+        ]);
+
+        assert_eq!(serde_json::to_string(&actions[1]).unwrap(), r#"{"Exchange":1}"#);
+    }
+}
@@ -1,5 +1,6 @@
 // information set monte carlo tree search
 
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use petgraph::{Directed};
@@ -8,37 +9,39 @@ use petgraph::prelude::StableGraph;
 use rand::{SeedableRng, Rng};
 use rand_pcg::Pcg64;
 use crate::action::Action;
-use crate::{Coup};
+use crate::{Coup, GameConfig, GameOutcome};
 
-fn simulate<R: Rng + Sized>(game: &Coup, rng: &mut R) -> usize {
-    if let Some(winner) = game.winner() {
-        return winner;
-    }
-
-    let mut game = game.clone();
-
-    loop {
-        let mut actions = game.actions();
-        let random_index = rng.gen_range(0..actions.len());
-        let random_action = actions.remove(random_index);
+// plays randomly to the end from the given game, then walks back up so it's left as it was
+// rollouts that drag on past 100 turns are cut off and scored as they stand
+fn simulate<R: Rng + Sized>(game: &mut Coup, rng: &mut R) -> GameOutcome {
+    let mut undos = Vec::new();
 
-        game = game.apply_action(random_action, rng).unwrap();
-
-        if let Some(winner) = game.winner() {
-            return winner;
+    let outcome = loop {
+        if let Some(outcome) = game.outcome() {
+            break outcome;
         }
 
         if game.turn > 100 {
-            println!("failed to simulate in a reasonable amount of turns - default winner 0");
-            return 0;
+            break game.standings();
         }
+
+        let random_action = game.random_action(rng).expect("a game without a winner always has an action");
+
+        undos.push(game.apply_action_mut(random_action, rng).unwrap());
+    };
+
+    while let Some(undo) = undos.pop() {
+        game.undo(undo);
     }
+
+    outcome
 }
 
 fn ismcts<R: Rng + Sized + Clone + std::marker::Send>(game: &Coup, rng: &mut R, num_determinizations: usize, num_simulations: usize) -> Action {
 
     // actions should be the same between the determinization and the current game
     let actions = game.actions();
+    let player_idx = game.active_player_idx();
 
     let determinization_scores: Arc<Mutex<Vec<Vec<Vec<f32>>>>> = Arc::new(Mutex::new(Vec::new()));
 
@@ -56,18 +59,23 @@ fn ismcts<R: Rng + Sized + Clone + std::marker::Send>(game: &Coup, rng: &mut R,
                 let determinization_scores_ref_clone = determinization_scores.clone();
 
                 scope.spawn(move || {
-                    let game = game.determine(&mut rng, game.current_player_idx);
+                    let mut game = game.determine(&mut rng, player_idx);
                     let mut action_scores: Vec<Vec<f32>> = actions.iter().map(|_| vec![]).collect();
 
                     for (action_idx, action) in actions.iter().enumerate() {
-                        let game_after_action = game.apply_action(action.clone(), &mut rng).unwrap();
+                        let undo = game.apply_action_mut(*action, &mut rng).unwrap();
 
                         let mut scores: Vec<f32> = game.players.iter().map(|_| 0f32).collect();
                         for _simulation_count in 0..num_simulations {
-                            let winner_player_idx = simulate(&game_after_action, &mut rng);
-                            scores[winner_player_idx] += 1f32;
+                            // placing counts, not just winning
+                            let outcome = simulate(&mut game, &mut rng);
+                            for (player_idx, score) in scores.iter_mut().enumerate() {
+                                *score += outcome.score(player_idx);
+                            }
                         }
 
+                        game.undo(undo);
+
                         let max = scores.iter().fold(0f32, |sum, &val| if sum > val { sum } else { val });
                         let normalized: Vec<f32> = scores.iter().map(|&n| n / max).collect();
 
@@ -96,16 +104,16 @@ fn ismcts<R: Rng + Sized + Clone + std::marker::Send>(game: &Coup, rng: &mut R,
 
     let mut diff: Vec<(usize, f32)> = avg_scores.iter().enumerate().map(|scores| {
         let num_opps = (game.players.len() - 1) as f32;
-        let sum_opps_score = scores.1.iter().enumerate().filter(|(idx, _)| *idx != game.current_player_idx).map(|(_, e)| e).sum::<f32>();
+        let sum_opps_score = scores.1.iter().enumerate().filter(|(idx, _)| *idx != player_idx).map(|(_, e)| e).sum::<f32>();
         let avg_opps_score = sum_opps_score / num_opps;
-        (scores.0, scores.1[game.current_player_idx] - avg_opps_score)
+        (scores.0, scores.1[player_idx] - avg_opps_score)
     }).collect();
 
     diff.sort_by(|a, b| {
         b.1.partial_cmp(&a.1).unwrap()
     });
 
-    actions[diff[0].0].clone()
+    actions[diff[0].0]
 }
 
 #[derive(Clone, Eq, PartialEq)]
@@ -128,6 +136,7 @@ pub struct SimPlayerParams {
 }
 
 pub struct SimParams {
+    pub config: GameConfig,
     pub seed: u64,
     pub num_sims: usize,
     pub sim_players: Vec<SimPlayerParams>,
@@ -136,6 +145,7 @@ pub struct SimParams {
 impl Default for SimParams {
     fn default() -> Self {
         Self {
+            config: GameConfig::official(3),
             seed: 0,
             num_sims: 1,
             sim_players: vec![
@@ -158,29 +168,17 @@ impl Default for SimParams {
 
 fn add_state_to_graph(
     graph: &mut StableGraph<GraphNode, GraphEdge, Directed>,
-    nodes: &mut Vec<(NodeIndex, GraphNode)>,
+    nodes: &mut HashMap<Coup, NodeIndex>,
     game: &Coup,
     sim_n: usize,
     step: usize,
 ) -> NodeIndex {
-    let node = GraphNode {
+    // the same position reached again, maybe in another sim, is the same node
+    *nodes.entry(game.clone()).or_insert_with(|| graph.add_node(GraphNode {
         sim: sim_n,
-        step: step,
+        step,
         state: game.clone(),
-    };
-
-    let node_index = {
-        let existing = nodes.iter().find(|n| n.1.state == node.state);
-        if let Some(existing) = existing {
-            existing.0
-        } else {
-            graph.add_node(node.clone())
-        }
-    };
-
-    nodes.push((node_index, node));
-
-    node_index
+    }))
 }
 
 fn add_action_to_graph(
@@ -192,59 +190,69 @@ fn add_action_to_graph(
     let existing_edge = graph.find_edge(prev_state_idx, new_state_idx);
     if let Some(existing_edge) = existing_edge {
         let edge = graph.edge_weight(existing_edge).unwrap();
-        graph.update_edge(prev_state_idx, new_state_idx, GraphEdge { action: action, count: edge.count + 1 });
+        graph.update_edge(prev_state_idx, new_state_idx, GraphEdge { action, count: edge.count + 1 });
     } else {
-        graph.add_edge(prev_state_idx, new_state_idx, GraphEdge { action: action, count: 1 });
+        graph.add_edge(prev_state_idx, new_state_idx, GraphEdge { action, count: 1 });
     }
 }
 
 pub fn generate_graph(sim_params: SimParams) -> StableGraph<GraphNode, GraphEdge, Directed> {
+    assert_eq!(sim_params.config.num_players as usize, sim_params.sim_players.len(), "every player needs sim params");
+
     let mut graph: StableGraph<GraphNode, GraphEdge, Directed> = StableGraph::new();
-    let mut nodes: Vec<(NodeIndex, GraphNode)> = Vec::new();
+    let mut nodes: HashMap<Coup, NodeIndex> = HashMap::new();
 
     for sim_n in 0..sim_params.num_sims {
         let mut not_rng = Pcg64::seed_from_u64(sim_params.seed);
         let mut per_sim_rng = Pcg64::seed_from_u64(sim_params.seed + (sim_n as u64));
 
-        let mut game = Coup::new(sim_params.sim_players.len() as u8, &mut not_rng);
+        let mut game = Coup::with_config(sim_params.config, &mut not_rng).unwrap();
         let mut step = 0usize;
 
-        add_state_to_graph(&mut graph, &mut nodes, &mut game, sim_n, step);
+        let mut prev_node_idx = add_state_to_graph(&mut graph, &mut nodes, &game, sim_n, step);
 
         step += 1;
 
         loop {
-            let sim_player = &sim_params.sim_players[game.current_player_idx];
+            let sim_player = &sim_params.sim_players[game.active_player_idx()];
             let ai_selected_action = ismcts(&game, &mut per_sim_rng, sim_player.num_determinations, sim_player.num_simulations_per_action);
 
-            let prev_node_idx = nodes.last().unwrap().0;
-
-            game = game.apply_action(ai_selected_action.clone(), &mut per_sim_rng).unwrap();
+            game = game.apply_action(ai_selected_action, &mut per_sim_rng).unwrap();
 
             match ai_selected_action {
-                Action::Propose(_player_id, _) |
                 Action::Income(_player_id) |
                 Action::ForeignAid(_player_id) |
                 Action::Tax(_player_id) |
                 Action::Assassinate(_player_id, _) |
                 Action::Coup(_player_id, _) |
                 Action::Steal(_player_id, _) |
-                Action::Exchange(_player_id, _) |
+                Action::Exchange(_player_id) |
                 Action::Block(_player_id, _) |
                 Action::Relent(_player_id) |
                 Action::Challenge(_player_id) |
                 Action::Lose(_player_id, _) |
                 Action::Reveal(_player_id, _) |
-                Action::Resolve(_player_id) => {
+                Action::Resolve(_player_id) |
+                Action::Keep(_player_id, _, _) |
+                Action::ChooseInfluence(_player_id, _) |
+                Action::Convert(_player_id, _) |
+                Action::Embezzle(_player_id) |
+                Action::Disprove(_player_id) |
+                Action::Examine(_player_id, _) |
+                Action::Show(_player_id, _) |
+                Action::ForceSwap(_player_id) |
+                Action::Release(_player_id) |
+                Action::Leave(_player_id, _) => {
                     let new_node_idx = add_state_to_graph(&mut graph, &mut nodes, &game, sim_n, step);
-                    add_action_to_graph(&mut graph, ai_selected_action.clone(), prev_node_idx, new_node_idx);
+                    add_action_to_graph(&mut graph, ai_selected_action, prev_node_idx, new_node_idx);
+                    prev_node_idx = new_node_idx;
                 }
-                _ => {}
+                Action::Pass(_player_id) => {}
             }
 
             step += 1;
 
-            if let Some(_winner) = game.winner() {
+            if game.is_over() {
                 break;
             }
         }
@@ -256,10 +264,28 @@ pub fn generate_graph(sim_params: SimParams) -> StableGraph<GraphNode, GraphEdge
 
 #[cfg(test)]
 mod tests {
-    use crate::ai::{generate_graph, SimParams};
+    use crate::ai::{generate_graph, SimParams, SimPlayerParams};
+    use crate::GameConfig;
 
     #[test]
     fn run_test_simulation() {
         generate_graph(SimParams::default());
     }
+
+    #[test]
+    fn run_two_player_simulation() {
+        let sim_player = SimPlayerParams {
+            num_determinations: 4,
+            num_simulations_per_action: 20,
+        };
+
+        let graph = generate_graph(SimParams {
+            config: GameConfig::two_player(),
+            sim_players: vec![sim_player.clone(), sim_player],
+            ..SimParams::default()
+        });
+
+        // the game goes on past both players picking their influence
+        assert!(graph.node_count() > 3);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use crate::{Action, Character, Coup, CoupError, Faction, GameConfig, Player, SetupMode, State};
+use crate::history::History;
+use crate::inline_vec::InlineVec;
+use crate::known_cards::KnownCards;
+use crate::zobrist::Zobrist;
+
+// sets up an exact position without any randomness, for regression tests and puzzles
+// anything that isn't set is filled in the same way every time - hands are dealt from the cards nobody
+// was given, in character order, and whatever is left over makes up the deck
+#[derive(Clone, Debug)]
+pub struct CoupBuilder {
+    config: GameConfig,
+    hands: BTreeMap<usize, Vec<(Character, bool)>>,
+    coins: BTreeMap<usize, u8>,
+    factions: BTreeMap<usize, Faction>,
+    deck: Option<Vec<Character>>,
+    turn: usize,
+    current_player_idx: usize,
+    state: State,
+    priority_player_idx: Option<usize>,
+    proposal: Option<Action>,
+    proposal_blocked_with: Option<Character>,
+    treasury_reserve: u8,
+}
+
+impl CoupBuilder {
+    pub fn new(num_players: u8) -> Self {
+        Self::with_config(GameConfig::official(num_players))
+    }
+
+    pub fn with_config(config: GameConfig) -> Self {
+        Self {
+            config,
+            hands: BTreeMap::new(),
+            coins: BTreeMap::new(),
+            factions: BTreeMap::new(),
+            deck: None,
+            turn: 0,
+            current_player_idx: 0,
+            state: State::AwaitingProposal,
+            priority_player_idx: None,
+            proposal: None,
+            proposal_blocked_with: None,
+            treasury_reserve: 0,
+        }
+    }
+
+    // two live cards
+    pub fn hand(self, player_idx: usize, first: Character, second: Character) -> Self {
+        self.cards(player_idx, &[(first, false), (second, false)])
+    }
+
+    // (character, revealed) for every card in the player's hand
+    pub fn cards(mut self, player_idx: usize, cards: &[(Character, bool)]) -> Self {
+        self.hands.insert(player_idx, cards.to_vec());
+        self
+    }
+
+    pub fn coins(mut self, player_idx: usize, coins: u8) -> Self {
+        self.coins.insert(player_idx, coins);
+        self
+    }
+
+    pub fn faction(mut self, player_idx: usize, faction: Faction) -> Self {
+        self.factions.insert(player_idx, faction);
+        self
+    }
+
+    // every card that isn't in a hand, in order
+    pub fn deck(mut self, deck: &[Character]) -> Self {
+        self.deck = Some(deck.to_vec());
+        self
+    }
+
+    pub fn turn(mut self, turn: usize) -> Self {
+        self.turn = turn;
+        self
+    }
+
+    pub fn current_player(mut self, player_idx: usize) -> Self {
+        self.current_player_idx = player_idx;
+        self
+    }
+
+    // the player who has to make the next decision is worked out from the phase, unless it's set with priority_player()
+    pub fn phase(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn priority_player(mut self, player_idx: usize) -> Self {
+        self.priority_player_idx = Some(player_idx);
+        self
+    }
+
+    // the action the current player is in the middle of
+    pub fn proposal(mut self, proposal: Action) -> Self {
+        self.proposal = Some(proposal);
+        self
+    }
+
+    pub fn blocked_with(mut self, character: Character) -> Self {
+        self.proposal_blocked_with = Some(character);
+        self
+    }
+
+    pub fn treasury_reserve(mut self, coins: u8) -> Self {
+        self.treasury_reserve = coins;
+        self
+    }
+
+    pub fn build(self) -> Result<Coup, CoupError> {
+        self.config.validate()?;
+        let num_players = self.config.num_players as usize;
+
+        for &player_idx in self.hands.keys().chain(self.coins.keys()).chain(self.factions.keys()) {
+            if player_idx >= num_players {
+                return Err(CoupError::NoSuchPlayer(player_idx));
+            }
+        }
+
+        // the exchange pool only exists while an exchange is being played out
+        if self.state == State::AwaitingExchangeChoice {
+            return Err(CoupError::InvalidSetup(format!("can't start a game at {:?}", self.state)));
+        }
+
+        let selecting = match self.state {
+            State::AwaitingInfluenceSelection(_) if self.config.setup != SetupMode::TwoPlayer => {
+                return Err(CoupError::InvalidSetup("only two player games pick their starting influence".to_string()));
+            }
+            State::AwaitingInfluenceSelection(selecting_player_idx) => Some(selecting_player_idx),
+            _ => None,
+        };
+
+        // while influence is being picked, the players before the one picking have one card and the rest none
+        let hand_size = |player_idx: usize| match selecting {
+            Some(selecting_player_idx) => usize::from(player_idx < selecting_player_idx),
+            None => 2,
+        };
+
+        if !self.config.reformation && (!self.factions.is_empty() || self.treasury_reserve > 0) {
+            return Err(CoupError::InvalidSetup("factions and the treasury reserve are only in reformation games".to_string()));
+        }
+
+        // every card in the game has to be somewhere, exactly once
+        let mut unused: Vec<Character> = self.config.characters().iter()
+            .flat_map(|&card| std::iter::repeat_n(card, self.config.copies_per_character as usize))
+            .collect();
+        let mut take = |card: Character| match unused.iter().position(|&unused_card| unused_card == card) {
+            Some(unused_idx) => Ok(unused.remove(unused_idx)),
+            None => Err(CoupError::InvalidSetup(format!("there are more {:?} cards than the game has", card))),
+        };
+
+        for (&player_idx, cards) in &self.hands {
+            if cards.len() != hand_size(player_idx) {
+                return Err(CoupError::InvalidSetup(format!("player {player_idx} has {} cards instead of {}", cards.len(), hand_size(player_idx))));
+            }
+
+            for &(card, _) in cards {
+                take(card)?;
+            }
+        }
+
+        if let Some(deck) = &self.deck {
+            for &card in deck {
+                take(card)?;
+            }
+        }
+
+        let mut players = InlineVec::new();
+        for player_idx in 0..num_players {
+            let influence_cards = match self.hands.get(&player_idx) {
+                Some(cards) => InlineVec::from_slice(cards),
+                None if unused.len() < hand_size(player_idx) => {
+                    return Err(CoupError::InvalidSetup(format!("there are no cards left to deal player {player_idx}")));
+                }
+                None => unused.drain(..hand_size(player_idx)).map(|card| (card, false)).collect(),
+            };
+
+            players.push(Player {
+                money: self.coins.get(&player_idx).copied().unwrap_or(self.config.starting_coins(player_idx)),
+                influence_cards,
+                faction: self.factions.get(&player_idx).copied().or(self.config.starting_faction(player_idx)),
+            });
+        }
+
+        let deck = match self.deck {
+            Some(_) if !unused.is_empty() => {
+                return Err(CoupError::InvalidSetup(format!("{:?} aren't in a hand or the deck", unused)));
+            }
+            Some(deck) => InlineVec::from_slice(&deck),
+            None => InlineVec::from_slice(&unused),
+        };
+
+        // anyone who starts out of the game went out before it began
+        let eliminations = (0..num_players)
+            .filter(|&player_idx| hand_size(player_idx) > 0 && !players[player_idx].is_alive())
+            .map(|player_idx| (player_idx, 0))
+            .collect();
+
+        let mut game = Coup {
+            config: self.config,
+            turn: self.turn,
+            current_player_idx: self.current_player_idx,
+            deck,
+            players,
+            state: self.state,
+            priority_player_idx: None,
+            proposal: self.proposal,
+            proposal_blocked_with: self.proposal_blocked_with,
+            exchange_cards: InlineVec::new(),
+            treasury_reserve: self.treasury_reserve,
+            known_cards: KnownCards::default(),
+            pending_draws: InlineVec::new(),
+            eliminations,
+            history: History::default(),
+            zobrist: Zobrist::default(),
+        };
+
+        // the default priority goes around the table from the current player, so they have to be at it
+        if selecting.is_none() {
+            game.check_player_alive(self.current_player_idx)?;
+        }
+
+        game.priority_player_idx = match self.priority_player_idx {
+            Some(priority_player_idx) => Some(priority_player_idx),
+            None => game.default_priority_player_idx(),
+        };
+
+        // everything else about the phase is down to validate()
+        game.rehash();
+        game.validate()?;
+        Ok(game)
+    }
+}
+
+impl Coup {
+    fn check_player_alive(&self, player_idx: usize) -> Result<(), CoupError> {
+        if player_idx >= self.players.len() {
+            return Err(CoupError::NoSuchPlayer(player_idx));
+        }
+
+        if self.is_player_dead(player_idx) {
+            return Err(CoupError::DeadTarget(player_idx));
+        }
+
+        Ok(())
+    }
+
+    // who the game would have given the decision to on getting to this phase
+    fn default_priority_player_idx(&mut self) -> Option<usize> {
+        match self.state {
+            State::AwaitingProposal => None,
+            State::AwaitingProposalResponse(_) | State::AwaitingProposalBlockResponse(_) => {
+                self.go_next_prio();
+                self.priority_player_idx
+            }
+            State::AwaitingChallengedBlockResponse(blocker_player_idx, _) => Some(blocker_player_idx),
+            State::AwaitingLoseInfluence(player_idx, _) |
+            State::AwaitingExamineShow(player_idx) |
+            State::AwaitingInfluenceSelection(player_idx) => Some(player_idx),
+            _ => Some(self.current_player_idx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use crate::{Action, Coup, CoupBuilder, CoupError, GameConfig, State};
+    use crate::Character::{Ambassador, Assassin, Captain, Contessa, Duke, Inquisitor};
+
+    #[test]
+    fn builds_exact_positions() {
+        let coup = CoupBuilder::new(3)
+            .hand(0, Assassin, Duke)
+            .cards(1, &[(Contessa, false), (Captain, true)])
+            .coins(0, 3)
+            .current_player(0)
+            .turn(4)
+            .build()
+            .unwrap();
+
+        assert_eq!(coup.player(0).influence_cards(), &[(Assassin, false), (Duke, false)]);
+        assert_eq!(coup.revealed_cards(1).collect::<Vec<_>>(), vec![Captain]);
+        assert_eq!(coup.coins(0), 3);
+        assert_eq!(coup.coins(1), 2);
+        assert_eq!(coup.turn(), 4);
+        assert_eq!(coup.deck_size(), 15 - 6);
+
+        // the rest is filled in the same way every time
+        assert_eq!(coup, CoupBuilder::new(3).hand(0, Assassin, Duke).cards(1, &[(Contessa, false), (Captain, true)]).coins(0, 3).turn(4).build().unwrap());
+
+        // and plays like any other game
+        let coup = coup.apply_action(Action::Assassinate(0, 1), &mut thread_rng()).unwrap();
+        assert_eq!(coup.state(), &State::AwaitingProposalResponse(2));
+        assert_eq!(coup.priority_player_idx(), Some(1));
+    }
+
+    #[test]
+    fn builds_mid_turn() {
+        let deck = [Duke, Assassin, Assassin, Captain, Captain, Ambassador, Ambassador, Contessa, Contessa];
+        let coup = CoupBuilder::new(3)
+            .hand(0, Captain, Duke)
+            .hand(1, Ambassador, Contessa)
+            .hand(2, Duke, Assassin)
+            .deck(&deck)
+            .proposal(Action::Steal(0, 1))
+            .phase(State::AwaitingProposalBlockResponse(1))
+            .blocked_with(Ambassador)
+            .build()
+            .unwrap();
+
+        // the blocker doesn't get to challenge their own block
+        assert_eq!(coup.priority_player_idx(), Some(2));
+        assert_eq!(coup.actions(), vec![Action::Challenge(2), Action::Pass(2)]);
+        assert_eq!(coup.deck_size(), deck.len());
+    }
+
+    #[test]
+    fn builds_two_player_selection() {
+        let mut rng = thread_rng();
+        let coup = CoupBuilder::with_config(GameConfig::two_player()).phase(State::AwaitingInfluenceSelection(0)).build().unwrap();
+        let mut dealt = Coup::with_config(GameConfig::two_player(), &mut rng).unwrap();
+        dealt.deck = coup.deck;
+        dealt.rehash();
+        assert_eq!(coup, dealt);
+
+        let coup = CoupBuilder::with_config(GameConfig::two_player())
+            .phase(State::AwaitingInfluenceSelection(1))
+            .cards(0, &[(Contessa, false)])
+            .build()
+            .unwrap();
+        assert_eq!(coup.priority_player_idx(), Some(1));
+        assert_eq!(coup.player(1).influence_cards(), &[]);
+        assert!(coup.actions().contains(&Action::ChooseInfluence(1, Contessa)));
+    }
+
+    #[test]
+    fn rejects_impossible_setups() {
+        let invalid_setup = |result: Result<_, CoupError>| matches!(result, Err(CoupError::InvalidSetup(_)));
+        let invalid_state = |result: Result<_, CoupError>| matches!(result, Err(CoupError::InvalidState(_)));
+
+        // only three dukes in the game
+        assert!(invalid_setup(CoupBuilder::new(3).hand(0, Duke, Duke).hand(1, Duke, Duke).build()));
+        assert!(invalid_setup(CoupBuilder::new(3).hand(0, Inquisitor, Duke).build()));
+        assert!(invalid_setup(CoupBuilder::new(3).cards(0, &[(Duke, false)]).build()));
+
+        // the deck has to hold every card nobody has
+        assert!(invalid_setup(CoupBuilder::new(3).deck(&[Duke]).build()));
+        let full_deck = [Duke, Duke, Duke, Assassin, Assassin, Assassin, Captain, Captain, Captain, Ambassador, Ambassador, Ambassador, Contessa];
+        assert!(invalid_setup(CoupBuilder::new(3).hand(0, Contessa, Contessa).deck(&full_deck).build()));
+
+        // phases the turn can't be in are caught by validate()
+        assert!(invalid_state(CoupBuilder::new(3).phase(State::ResolveProposal).build()));
+        assert!(invalid_state(CoupBuilder::new(3).proposal(Action::Tax(0)).phase(State::AwaitingProposalBlockResponse(1)).build()));
+        assert!(invalid_state(CoupBuilder::new(3).proposal(Action::Income(0)).phase(State::ResolveProposal).build()));
+        assert!(invalid_state(CoupBuilder::new(3).proposal(Action::Tax(1)).phase(State::ResolveProposal).build()));
+        assert!(invalid_state(CoupBuilder::new(3).cards(1, &[(Duke, true), (Captain, true)]).proposal(Action::Steal(0, 2)).phase(State::AwaitingChallengedProposalResponse(1)).build()));
+        assert!(invalid_setup(CoupBuilder::new(3).faction(0, crate::Faction::Reformist).build()));
+
+        assert_eq!(CoupBuilder::new(3).coins(3, 5).build().unwrap_err(), CoupError::NoSuchPlayer(3));
+        assert_eq!(CoupBuilder::new(3).cards(0, &[(Duke, true), (Captain, true)]).build().unwrap_err(), CoupError::DeadTarget(0));
+        assert!(matches!(CoupBuilder::new(11).build(), Err(CoupError::InvalidPlayerCount(11))));
+        assert!(invalid_setup(CoupBuilder::new(2).phase(State::AwaitingInfluenceSelection(0)).build()));
+        assert!(invalid_setup(CoupBuilder::with_config(GameConfig::two_player()).phase(State::AwaitingInfluenceSelection(0)).hand(0, Duke, Captain).build()));
+
+        assert!(CoupBuilder::with_config(GameConfig::reformation(4)).faction(0, crate::Faction::Reformist).treasury_reserve(3).build().is_ok());
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use crate::{Character, Coup, CoupError, EventKind, Undo, Visibility};
+use crate::undo::Change;
+
+// a card that has to come off the deck before anyone can act
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Draw {
+    // (player, card index) - the card there went back into the deck and gets replaced
+    Influence(usize, usize),
+    // a card for the pool the player is exchanging with
+    Exchange(usize),
+}
+
+// only ever used to fill unused slots
+impl Default for Draw {
+    fn default() -> Self {
+        Draw::Exchange(0)
+    }
+}
+
+impl Coup {
+    // the draw the game is waiting on, if it's at a chance node
+    pub fn pending_draw(&self) -> Option<Draw> {
+        self.pending_draws.first().copied()
+    }
+
+    pub fn is_chance_node(&self) -> bool {
+        !self.pending_draws.is_empty()
+    }
+
+    // every character the pending draw can turn up, with how likely it is
+    // the deck is treated as unordered, so each card in it is equally likely to be drawn
+    pub fn chance_outcomes(&self) -> Vec<(Character, f64)> {
+        if !self.is_chance_node() {
+            return Vec::new();
+        }
+
+        let mut counts: Vec<(Character, usize)> = Vec::with_capacity(5);
+        for &card in &self.deck {
+            match counts.iter_mut().find(|(character, _)| *character == card) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((card, 1)),
+            }
+        }
+
+        counts.sort_by_key(|(character, _)| *character as usize);
+
+        counts.into_iter()
+            .map(|(character, count)| (character, count as f64 / self.deck.len() as f64))
+            .collect()
+    }
+
+    // resolves the pending draw with a specific card from the deck
+    pub fn apply_chance(&self, character: Character) -> Result<Coup, CoupError> {
+        self.check_chance(character)?;
+
+        let mut game = self.clone();
+        let mut undo = Undo::untracked(&game);
+        game.draw(character, &mut undo);
+        game.update_keys(&undo);
+
+        Ok(game)
+    }
+
+    // apply_chance() without the clone, the returned undo takes it back
+    pub fn apply_chance_mut(&mut self, character: Character) -> Result<Undo, CoupError> {
+        self.check_chance(character)?;
+
+        let mut undo = Undo::new(self);
+        self.draw(character, &mut undo);
+        self.update_keys(&undo);
+
+        Ok(undo)
+    }
+
+    fn check_chance(&self, character: Character) -> Result<(), CoupError> {
+        if !self.is_chance_node() {
+            return Err(CoupError::NoChancePending);
+        }
+
+        if !self.deck.contains(&character) {
+            return Err(CoupError::NotInDeck(character));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn draw(&mut self, character: Character, undo: &mut Undo) {
+        let draw = self.pending_draws.remove(0);
+        undo.push(Change::DrawResolved(draw));
+
+        let deck_idx = self.deck.iter().position(|&card| card == character).expect("drawn card must be in the deck");
+        self.deck.remove(deck_idx);
+        undo.push(Change::DeckRemoved(deck_idx, character));
+
+        let player_idx = match draw {
+            Draw::Influence(player_idx, card_idx) => {
+                let hand = &mut self.players[player_idx].influence_cards;
+                if card_idx < hand.len() {
+                    undo.push(Change::Card(player_idx, card_idx, hand[card_idx]));
+                    hand[card_idx] = (character, false);
+                } else {
+                    hand.push((character, false));
+                    undo.push(Change::CardPushed(player_idx));
+                }
+
+                player_idx
+            }
+            Draw::Exchange(player_idx) => {
+                self.exchange_cards.push(character);
+                undo.push(Change::ExchangeCardPushed);
+                player_idx
+            }
+        };
+
+        self.record(Visibility::Private(player_idx), EventKind::CardDrawn(player_idx, character));
+        debug_assert_eq!(self.validate(), Ok(()), "after drawing {:?}", character);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Action, CoupBuilder, CoupError, State};
+    use crate::chance::Draw;
+    use crate::Character::{Ambassador, Assassin, Captain, Contessa, Duke};
+
+    #[test]
+    fn reveal_is_a_chance_node() {
+        let mut coup = CoupBuilder::new(3).hand(0, Duke, Captain).build().unwrap();
+
+        coup = coup.apply_deterministic(Action::Tax(0)).unwrap();
+        coup = coup.apply_deterministic(Action::Challenge(1)).unwrap();
+        coup = coup.apply_deterministic(Action::Reveal(0, 0)).unwrap();
+
+        // the duke went back in, and a replacement has to be drawn before the challenger loses
+        assert_eq!(coup.pending_draw(), Some(Draw::Influence(0, 0)));
+        assert_eq!(coup.state, State::AwaitingLoseInfluence(1, false));
+        assert!(coup.actions().is_empty());
+        assert_eq!(coup.apply_deterministic(Action::Lose(1, 0)).unwrap_err(), CoupError::ChancePending);
+
+        let outcomes = coup.chance_outcomes();
+        let total: f64 = outcomes.iter().map(|(_, probability)| probability).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        let duke_count = coup.deck.iter().filter(|&&card| card == Duke).count();
+        let duke_probability = outcomes.iter().find(|(character, _)| *character == Duke).unwrap().1;
+        assert_eq!(duke_probability, duke_count as f64 / coup.deck.len() as f64);
+
+        // every outcome leads to a playable game with the drawn card in hand
+        for (character, _) in outcomes {
+            let next = coup.apply_chance(character).unwrap();
+            assert!(!next.is_chance_node());
+            assert_eq!(next.players[0].influence_cards[0], (character, false));
+            assert_eq!(next.deck.len(), coup.deck.len() - 1);
+            assert!(!next.actions().is_empty());
+        }
+
+        let drawn = coup.apply_chance(coup.deck[0]).unwrap();
+        assert_eq!(drawn.apply_chance(drawn.deck[0]).unwrap_err(), CoupError::NoChancePending);
+    }
+
+    #[test]
+    fn exchange_draws_are_chance_nodes() {
+        // the dukes and assassins are all in hand, so only three characters can come out
+        let mut coup = CoupBuilder::new(3)
+            .hand(0, Duke, Duke)
+            .hand(1, Duke, Assassin)
+            .hand(2, Assassin, Assassin)
+            .build()
+            .unwrap();
+
+        coup = coup.apply_deterministic(Action::Exchange(0)).unwrap();
+        coup = coup.apply_deterministic(Action::Pass(1)).unwrap();
+        coup = coup.apply_deterministic(Action::Pass(2)).unwrap();
+        coup = coup.apply_deterministic(Action::Resolve(0)).unwrap();
+
+        assert_eq!(coup.pending_draw(), Some(Draw::Exchange(0)));
+        assert_eq!(coup.chance_outcomes(), vec![(Captain, 1.0 / 3.0), (Ambassador, 1.0 / 3.0), (Contessa, 1.0 / 3.0)]);
+        assert_eq!(coup.apply_chance(Duke).unwrap_err(), CoupError::NotInDeck(Duke));
+
+        coup = coup.apply_chance(Contessa).unwrap();
+        assert_eq!(coup.chance_outcomes(), vec![(Captain, 3.0 / 8.0), (Ambassador, 3.0 / 8.0), (Contessa, 2.0 / 8.0)]);
+
+        coup = coup.apply_chance(Captain).unwrap();
+        assert_eq!(*coup.exchange_cards, [Contessa, Captain]);
+        assert!(coup.actions().iter().all(|action| matches!(action, Action::Keep(0, _, _))));
+    }
+}
@@ -0,0 +1,384 @@
+use std::sync::Mutex;
+use rand::Rng;
+use crate::{Action, Character, Coup, CoupError, Draw, Faction, GameConfig, MAX_PENDING_DRAWS, Player, State};
+use crate::Character::{Ambassador, Assassin, Captain, Contessa, Duke, Inquisitor};
+use crate::config::{MAX_CARDS, MAX_EXCHANGE_DRAW, MAX_PLAYERS};
+use crate::history::History;
+use crate::undo::Undo;
+use crate::zobrist::Zobrist;
+use crate::known_cards::KnownCards;
+
+// a game packed into bit fields - cheap to copy, hash and compare, and small enough to keep millions
+// of around for simulating and exploring at scale
+// converts to and from Coup without losing anything but the history, which isn't part of the position
+//
+// cards are 3 bits each, by their place in CHARACTERS
+// a hand is 4 bits a card, the character then whether it's revealed, with the number of cards above them
+// a pending draw is 6 bits, the player then the card index or EXCHANGE_DRAW
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CompactCoup {
+    // every game played with the same config shares it, see intern()
+    config: &'static GameConfig,
+    turn: u32,
+    current_player_idx: u8,
+    priority_player_idx: Option<u8>,
+    // the variant then its fields, see pack_state()
+    state: [u8; 3],
+    // the kind of action then its player and target, all zero when there's none, see pack_proposal()
+    proposal: [u8; 3],
+    proposal_blocked_with: Option<Character>,
+    hands: [u16; MAX_PLAYERS as usize],
+    money: [u8; MAX_PLAYERS as usize],
+    // 2 bits a player, nothing, loyalist or reformist
+    factions: u32,
+    // top card in the lowest bits
+    deck: u128,
+    deck_len: u8,
+    exchange_cards: u16,
+    exchange_cards_len: u8,
+    treasury_reserve: u8,
+    known_cards: KnownCards,
+    pending_draws: u64,
+    pending_draws_len: u8,
+    // players in the order they went out, and the turns they went out on
+    eliminated: [u8; MAX_PLAYERS as usize],
+    eliminated_on: [u32; MAX_PLAYERS as usize],
+    eliminated_len: u8,
+}
+
+const CHARACTERS: [Character; 6] = [Duke, Assassin, Captain, Ambassador, Contessa, Inquisitor];
+const CARD_BITS: u32 = 3;
+const CARD_MASK: u128 = 0b111;
+const HAND_CARD_BITS: u32 = 4;
+const HAND_LEN_SHIFT: u32 = 8;
+const DRAW_BITS: u32 = 6;
+// the card index slot of a pending draw for an exchange
+const EXCHANGE_DRAW: u64 = 2;
+
+// the biggest deck, exchange and set of draws have to fit
+const _: () = assert!(MAX_CARDS as u32 * CARD_BITS <= u128::BITS);
+const _: () = assert!(MAX_EXCHANGE_DRAW as u32 * CARD_BITS <= u16::BITS);
+const _: () = assert!(MAX_PENDING_DRAWS as u32 * DRAW_BITS <= u64::BITS && MAX_PLAYERS <= 16);
+
+// every config a game has been packed with, so each game only has to point at its own
+static CONFIGS: Mutex<Vec<&'static GameConfig>> = Mutex::new(Vec::new());
+
+fn intern(config: GameConfig) -> &'static GameConfig {
+    let mut configs = CONFIGS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match configs.iter().find(|&&interned| *interned == config) {
+        Some(&interned) => interned,
+        None => {
+            let interned = Box::leak(Box::new(config));
+            configs.push(interned);
+            interned
+        }
+    }
+}
+
+impl CompactCoup {
+    pub fn actions(&self) -> Vec<Action> {
+        self.unpack(History::disabled()).actions()
+    }
+
+    pub fn random_action<R: Rng + Sized>(&self, rng: &mut R) -> Option<Action> {
+        self.unpack(History::disabled()).random_action(rng)
+    }
+
+    // the same as Coup::winner(), read straight off the hands
+    pub fn winner(&self) -> Option<usize> {
+        if let State::AwaitingInfluenceSelection(_) = unpack_state(self.state) {
+            return None;
+        }
+
+        let mut alive = (0..self.config.num_players as usize).filter(|&player_idx| {
+            let hand = self.hands[player_idx];
+            let len = (hand >> HAND_LEN_SHIFT) as u32;
+            (0..len).any(|card_idx| hand & (1 << (card_idx * HAND_CARD_BITS + CARD_BITS)) == 0)
+        });
+
+        match (alive.next(), alive.next()) {
+            (Some(winner), None) => Some(winner),
+            _ => None,
+        }
+    }
+
+    pub fn turn(&self) -> usize {
+        self.turn as usize
+    }
+
+    pub fn apply_action<R: Rng + Sized>(&self, action: Action, rng: &mut R) -> Result<CompactCoup, CoupError> {
+        let mut game = self.unpack(History::disabled());
+
+        let mut undo = Undo::untracked(&game);
+        game.transition(action, &mut undo)?;
+        game.draw_randomly(rng, &mut undo);
+
+        // the config can't change, so there's no need to look it up again
+        Ok(CompactCoup::pack(&game, self.config))
+    }
+
+    fn pack(game: &Coup, config: &'static GameConfig) -> Self {
+        let mut hands = [0; MAX_PLAYERS as usize];
+        let mut money = [0; MAX_PLAYERS as usize];
+        let mut factions = 0;
+        for (player_idx, player) in game.players.iter().enumerate() {
+            let mut hand = (player.influence_cards.len() as u16) << HAND_LEN_SHIFT;
+            for (card_idx, &(character, revealed)) in player.influence_cards.iter().enumerate() {
+                let card = character as u16 | (u16::from(revealed) << CARD_BITS);
+                hand |= card << (card_idx as u32 * HAND_CARD_BITS);
+            }
+
+            hands[player_idx] = hand;
+            money[player_idx] = player.money;
+            factions |= pack_faction(player.faction) << (player_idx * 2);
+        }
+
+        let mut eliminated = [0; MAX_PLAYERS as usize];
+        let mut eliminated_on = [0; MAX_PLAYERS as usize];
+        for (elimination_idx, &(player_idx, turn)) in game.eliminations.iter().enumerate() {
+            eliminated[elimination_idx] = player_idx as u8;
+            eliminated_on[elimination_idx] = turn as u32;
+        }
+
+        Self {
+            config,
+            turn: game.turn as u32,
+            current_player_idx: game.current_player_idx as u8,
+            priority_player_idx: game.priority_player_idx.map(|player_idx| player_idx as u8),
+            state: pack_state(game.state),
+            proposal: pack_proposal(game.proposal),
+            proposal_blocked_with: game.proposal_blocked_with,
+            hands,
+            money,
+            factions,
+            deck: pack_cards(&game.deck),
+            deck_len: game.deck.len() as u8,
+            exchange_cards: pack_cards(&game.exchange_cards) as u16,
+            exchange_cards_len: game.exchange_cards.len() as u8,
+            treasury_reserve: game.treasury_reserve,
+            known_cards: game.known_cards,
+            pending_draws: game.pending_draws.iter().enumerate().fold(0, |packed, (draw_idx, &draw)| {
+                let draw = match draw {
+                    Draw::Influence(player_idx, card_idx) => (player_idx as u64) << 2 | card_idx as u64,
+                    Draw::Exchange(player_idx) => (player_idx as u64) << 2 | EXCHANGE_DRAW,
+                };
+                packed | draw << (draw_idx as u32 * DRAW_BITS)
+            }),
+            pending_draws_len: game.pending_draws.len() as u8,
+            eliminated,
+            eliminated_on,
+            eliminated_len: game.eliminations.len() as u8,
+        }
+    }
+
+    pub(crate) fn unpack(&self, history: History) -> Coup {
+        let players = (0..self.config.num_players as usize).map(|player_idx| {
+            let hand = self.hands[player_idx];
+            let len = (hand >> HAND_LEN_SHIFT) as u32;
+            Player {
+                money: self.money[player_idx],
+                influence_cards: (0..len).map(|card_idx| {
+                    let card = hand >> (card_idx * HAND_CARD_BITS);
+                    (CHARACTERS[card as usize & CARD_MASK as usize], card & (1 << CARD_BITS) != 0)
+                }).collect(),
+                faction: unpack_faction(self.factions >> (player_idx * 2)),
+            }
+        }).collect();
+
+        Coup {
+            config: *self.config,
+            turn: self.turn as usize,
+            current_player_idx: self.current_player_idx as usize,
+            deck: unpack_cards(self.deck, self.deck_len).collect(),
+            players,
+            state: unpack_state(self.state),
+            priority_player_idx: self.priority_player_idx.map(usize::from),
+            proposal: unpack_proposal(self.proposal),
+            proposal_blocked_with: self.proposal_blocked_with,
+            exchange_cards: unpack_cards(self.exchange_cards as u128, self.exchange_cards_len).collect(),
+            treasury_reserve: self.treasury_reserve,
+            known_cards: self.known_cards,
+            pending_draws: (0..self.pending_draws_len as u32).map(|draw_idx| {
+                let draw = self.pending_draws >> (draw_idx * DRAW_BITS);
+                let player_idx = (draw >> 2 & 0b1111) as usize;
+                match draw & 0b11 {
+                    EXCHANGE_DRAW => Draw::Exchange(player_idx),
+                    card_idx => Draw::Influence(player_idx, card_idx as usize),
+                }
+            }).collect(),
+            eliminations: (0..self.eliminated_len as usize)
+                .map(|elimination_idx| (self.eliminated[elimination_idx] as usize, self.eliminated_on[elimination_idx] as usize))
+                .collect(),
+            history,
+            zobrist: Zobrist::default(),
+        }
+    }
+}
+
+impl From<&Coup> for CompactCoup {
+    fn from(game: &Coup) -> Self {
+        CompactCoup::pack(game, intern(game.config))
+    }
+}
+
+// the history starts over from here
+impl From<CompactCoup> for Coup {
+    fn from(game: CompactCoup) -> Self {
+        let mut game = game.unpack(History::default());
+        game.rehash();
+        game
+    }
+}
+
+fn pack_cards(cards: &[Character]) -> u128 {
+    cards.iter().enumerate().fold(0, |packed, (card_idx, &character)| packed | (character as u128) << (card_idx as u32 * CARD_BITS))
+}
+
+fn unpack_cards(packed: u128, len: u8) -> impl Iterator<Item=Character> {
+    (0..len as u32).map(move |card_idx| CHARACTERS[(packed >> (card_idx * CARD_BITS) & CARD_MASK) as usize])
+}
+
+fn pack_faction(faction: Option<Faction>) -> u32 {
+    match faction {
+        None => 0,
+        Some(Faction::Loyalist) => 1,
+        Some(Faction::Reformist) => 2,
+    }
+}
+
+fn unpack_faction(packed: u32) -> Option<Faction> {
+    match packed & 0b11 {
+        0 => None,
+        1 => Some(Faction::Loyalist),
+        _ => Some(Faction::Reformist),
+    }
+}
+
+fn pack_state(state: State) -> [u8; 3] {
+    match state {
+        State::AwaitingProposal => [0, 0, 0],
+        State::AwaitingProposalResponse(num_remaining_passers) => [1, num_remaining_passers as u8, 0],
+        State::AwaitingProposalBlockResponse(blocker_player_idx) => [2, blocker_player_idx as u8, 0],
+        State::AwaitingChallengedBlockResponse(blocker_player_idx, challenger_player_idx) => [3, blocker_player_idx as u8, challenger_player_idx as u8],
+        State::AwaitingChallengedProposalResponse(challenger_player_idx) => [4, challenger_player_idx as u8, 0],
+        State::AwaitingLoseInfluence(loser_player_idx, end_turn) => [5, loser_player_idx as u8, end_turn as u8],
+        State::AwaitingExchangeChoice => [6, 0, 0],
+        State::AwaitingInfluenceSelection(player_idx) => [7, player_idx as u8, 0],
+        State::AwaitingExamineShow(examined_player_idx) => [8, examined_player_idx as u8, 0],
+        State::AwaitingExamineDecision(examined_player_idx, card_idx) => [9, examined_player_idx as u8, card_idx as u8],
+        State::ResolveProposal => [10, 0, 0],
+    }
+}
+
+fn unpack_state([variant, first, second]: [u8; 3]) -> State {
+    let (first, second) = (first as usize, second as usize);
+    match variant {
+        0 => State::AwaitingProposal,
+        1 => State::AwaitingProposalResponse(first),
+        2 => State::AwaitingProposalBlockResponse(first),
+        3 => State::AwaitingChallengedBlockResponse(first, second),
+        4 => State::AwaitingChallengedProposalResponse(first),
+        5 => State::AwaitingLoseInfluence(first, second != 0),
+        6 => State::AwaitingExchangeChoice,
+        7 => State::AwaitingInfluenceSelection(first),
+        8 => State::AwaitingExamineShow(first),
+        9 => State::AwaitingExamineDecision(first, second),
+        10 => State::ResolveProposal,
+        _ => unreachable!("not a packed state"),
+    }
+}
+
+// only proposals, and a coup while the target picks a card, are ever waiting to be resolved
+fn pack_proposal(proposal: Option<Action>) -> [u8; 3] {
+    let (kind, player_idx, target_player_idx) = match proposal {
+        None => return [0, 0, 0],
+        Some(Action::ForeignAid(player_idx)) => (1, player_idx, 0),
+        Some(Action::Tax(player_idx)) => (2, player_idx, 0),
+        Some(Action::Assassinate(player_idx, target_player_idx)) => (3, player_idx, target_player_idx),
+        Some(Action::Steal(player_idx, target_player_idx)) => (4, player_idx, target_player_idx),
+        Some(Action::Exchange(player_idx)) => (5, player_idx, 0),
+        Some(Action::Embezzle(player_idx)) => (6, player_idx, 0),
+        Some(Action::Examine(player_idx, target_player_idx)) => (7, player_idx, target_player_idx),
+        Some(Action::Coup(player_idx, target_player_idx)) => (8, player_idx, target_player_idx),
+        Some(action) => unreachable!("{action} can't be waiting to resolve"),
+    };
+
+    [kind, player_idx as u8, target_player_idx as u8]
+}
+
+fn unpack_proposal([kind, player_idx, target_player_idx]: [u8; 3]) -> Option<Action> {
+    let (player_idx, target_player_idx) = (player_idx as usize, target_player_idx as usize);
+    match kind {
+        0 => None,
+        1 => Some(Action::ForeignAid(player_idx)),
+        2 => Some(Action::Tax(player_idx)),
+        3 => Some(Action::Assassinate(player_idx, target_player_idx)),
+        4 => Some(Action::Steal(player_idx, target_player_idx)),
+        5 => Some(Action::Exchange(player_idx)),
+        6 => Some(Action::Embezzle(player_idx)),
+        7 => Some(Action::Examine(player_idx, target_player_idx)),
+        8 => Some(Action::Coup(player_idx, target_player_idx)),
+        _ => unreachable!("not a packed proposal"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::mem::size_of;
+    use rand::{Rng, thread_rng};
+    use crate::{Action, CompactCoup, Coup, GameConfig};
+
+    #[test]
+    fn converts_losslessly() {
+        let mut rng = thread_rng();
+        let configs = [GameConfig::official(6), GameConfig::reformation(4), GameConfig::inquisitor(5), GameConfig::two_player(), GameConfig::official(10)];
+
+        for config in configs {
+            for _ in 0..20 {
+                let mut coup = Coup::with_config(config, &mut rng).unwrap();
+
+                while coup.winner().is_none() && coup.turn < 200 {
+                    let compact = CompactCoup::from(&coup);
+                    assert_eq!(Coup::from(compact), coup);
+                    assert_eq!(compact.actions(), coup.actions());
+                    assert_eq!(compact.winner(), coup.winner());
+
+                    // chance nodes too
+                    let actions = coup.actions();
+                    let action = actions[rng.gen_range(0..actions.len())];
+                    let deterministic = coup.apply_deterministic(action).unwrap();
+                    assert_eq!(Coup::from(CompactCoup::from(&deterministic)), deterministic);
+
+                    coup = coup.apply_action(action, &mut rng).unwrap();
+                }
+
+                assert_eq!(CompactCoup::from(&coup).winner(), coup.winner());
+            }
+        }
+    }
+
+    #[test]
+    fn plays_a_game() {
+        let mut rng = thread_rng();
+        let mut compact = CompactCoup::from(&Coup::new(4, &mut rng).unwrap());
+        let mut seen = HashSet::new();
+
+        while compact.winner().is_none() {
+            seen.insert(compact);
+
+            let actions = compact.actions();
+            compact = compact.apply_action(actions[rng.gen_range(0..actions.len())], &mut rng).unwrap();
+        }
+
+        // the turn keeps moving, so there's at least a new position per turn
+        assert!(seen.len() >= compact.turn());
+        assert!(Coup::from(compact).winner().is_some());
+        assert!(compact.apply_action(Action::Income(0), &mut rng).is_err());
+    }
+
+    #[test]
+    fn is_smaller_than_a_game() {
+        assert!(size_of::<CompactCoup>() * 4 < size_of::<Coup>(), "{} bytes against {}", size_of::<CompactCoup>(), size_of::<Coup>());
+    }
+}
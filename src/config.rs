@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use crate::{Character, CoupError, Faction};
+use crate::Character::{Ambassador, Assassin, Captain, Contessa, Duke, Inquisitor};
+
+pub const MIN_PLAYERS: u8 = 2;
+pub const MAX_PLAYERS: u8 = 10;
+// the most cards a deck can be built from, and the most an exchange can draw
+pub const MAX_CARDS: usize = 40;
+pub const MAX_EXCHANGE_DRAW: u8 = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SetupMode {
+    // everyone is dealt two random cards
+    Standard,
+    // official heads-up rules - each player secretly picks one card from a full set of characters,
+    // then is dealt one random card, and the starting player gets one less coin
+    TwoPlayer,
+}
+
+// what happens to the coins of a player who leaves before they're knocked out, see Action::Leave
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LeaverCoins {
+    // back to the bank
+    #[default]
+    Returned,
+    // kept on the player's seat, out of play like a knocked out player's
+    Kept,
+}
+
+// the rule set a game is played with, so house rules don't need code changes
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GameConfig {
+    pub num_players: u8,
+    pub starting_money: u8,
+    pub copies_per_character: u8,
+    pub coup_cost: u8,
+    // at this much money a player must coup
+    pub forced_coup_threshold: u8,
+    pub assassination_cost: u8,
+    pub tax: u8,
+    pub foreign_aid: u8,
+    pub steal_amount: u8,
+    // how many cards an exchange draws from the deck
+    pub exchange_draw: u8,
+    pub setup: SetupMode,
+    // Reformation expansion - players are split into factions and the treasury reserve is in play
+    pub reformation: bool,
+    pub convert_self_cost: u8,
+    pub convert_other_cost: u8,
+    // the inquisitor replaces the ambassador
+    pub inquisitor: bool,
+    // house rule - nobody can hold more than this, whatever wouldn't fit stays where it came from
+    // the treasury reserve is capped too, with conversions paying the bank what it can't hold
+    #[serde(default)]
+    pub coin_cap: Option<u8>,
+    // house rule - once this many turns have been played without a winner the game is a draw
+    #[serde(default)]
+    pub turn_limit: Option<u16>,
+    #[serde(default)]
+    pub leaver_coins: LeaverCoins,
+}
+
+impl GameConfig {
+    pub fn official(num_players: u8) -> Self {
+        // bigger tables play with extra copies of every character so there's still a deck to draw from
+        let copies_per_character = match num_players {
+            0..=6 => 3,
+            7..=8 => 4,
+            _ => 5,
+        };
+
+        Self {
+            num_players,
+            starting_money: 2,
+            copies_per_character,
+            coup_cost: 7,
+            forced_coup_threshold: 10,
+            assassination_cost: 3,
+            tax: 3,
+            foreign_aid: 2,
+            steal_amount: 2,
+            exchange_draw: 2,
+            setup: SetupMode::Standard,
+            reformation: false,
+            convert_self_cost: 1,
+            convert_other_cost: 2,
+            inquisitor: false,
+            coin_cap: None,
+            turn_limit: None,
+            leaver_coins: LeaverCoins::Returned,
+        }
+    }
+
+    pub fn inquisitor(num_players: u8) -> Self {
+        Self {
+            inquisitor: true,
+            // the inquisitor only trades one card with the deck
+            exchange_draw: 1,
+            ..Self::official(num_players)
+        }
+    }
+
+    // every character in the deck for this rule set
+    pub fn characters(&self) -> [Character; 5] {
+        [Duke, Assassin, Captain, self.exchange_character(), Contessa]
+    }
+
+    // the character that claims the exchange action and can block stealing alongside the captain
+    pub fn exchange_character(&self) -> Character {
+        if self.inquisitor {
+            Inquisitor
+        } else {
+            Ambassador
+        }
+    }
+
+    pub fn reformation(num_players: u8) -> Self {
+        Self {
+            reformation: true,
+            ..Self::official(num_players)
+        }
+    }
+
+    pub fn two_player() -> Self {
+        Self {
+            setup: SetupMode::TwoPlayer,
+            ..Self::official(2)
+        }
+    }
+
+    // the rule sets with names, for writing them down - official, reformation, inquisitor and two-player
+    pub fn named(rules: &str, num_players: u8) -> Option<Self> {
+        match rules {
+            "official" => Some(Self::official(num_players)),
+            "reformation" => Some(Self::reformation(num_players)),
+            "inquisitor" => Some(Self::inquisitor(num_players)),
+            "two-player" => Some(Self::two_player()),
+            _ => None,
+        }
+    }
+
+    // factions alternate around the table
+    pub(crate) fn starting_faction(&self, player_idx: usize) -> Option<Faction> {
+        match (self.reformation, player_idx % 2) {
+            (false, _) => None,
+            (true, 0) => Some(Faction::Loyalist),
+            (true, _) => Some(Faction::Reformist),
+        }
+    }
+
+    pub(crate) fn starting_coins(&self, player_idx: usize) -> u8 {
+        match (self.setup, player_idx) {
+            (SetupMode::TwoPlayer, 0) => self.starting_money.saturating_sub(1),
+            _ => self.starting_money,
+        }
+    }
+
+    pub fn num_cards(&self) -> usize {
+        self.characters().len() * self.copies_per_character as usize
+    }
+
+    pub fn validate(&self) -> Result<(), CoupError> {
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&self.num_players) {
+            return Err(CoupError::InvalidPlayerCount(self.num_players));
+        }
+
+        // everyone is dealt two cards, and an exchange must always be able to draw
+        let cards_needed = self.num_players as usize * 2 + self.exchange_draw as usize;
+        if self.num_cards() < cards_needed {
+            return Err(CoupError::InvalidConfig(format!(
+                "{} players need at least {cards_needed} cards, but the deck only has {}",
+                self.num_players,
+                self.num_cards(),
+            )));
+        }
+
+        if self.num_cards() > MAX_CARDS {
+            return Err(CoupError::InvalidConfig(format!("the deck can have at most {MAX_CARDS} cards, not {}", self.num_cards())));
+        }
+
+        if self.exchange_draw == 0 || self.exchange_draw > MAX_EXCHANGE_DRAW {
+            return Err(CoupError::InvalidConfig(format!("an exchange must draw between 1 and {MAX_EXCHANGE_DRAW} cards")));
+        }
+
+        if self.coup_cost == 0 || self.forced_coup_threshold < self.coup_cost {
+            return Err(CoupError::InvalidConfig(format!(
+                "forced coup threshold ${} must be able to pay the coup cost ${}",
+                self.forced_coup_threshold,
+                self.coup_cost,
+            )));
+        }
+
+        if self.setup == SetupMode::TwoPlayer {
+            if self.num_players != 2 {
+                return Err(CoupError::InvalidConfig("two player setup needs exactly 2 players".to_string()));
+            }
+
+            // each player picks from their own full set of characters
+            if self.copies_per_character < 2 {
+                return Err(CoupError::InvalidConfig("two player setup needs a full set of characters per player".to_string()));
+            }
+        }
+
+        if let Some(coin_cap) = self.coin_cap {
+            if coin_cap < self.coup_cost || coin_cap < self.starting_money {
+                return Err(CoupError::InvalidConfig(format!("a coin cap of ${coin_cap} has to cover the starting money and the coup cost")));
+            }
+        }
+
+        if self.turn_limit == Some(0) {
+            return Err(CoupError::InvalidConfig("a turn limit has to allow at least one turn".to_string()));
+        }
+
+        if self.steal_amount == 0 {
+            return Err(CoupError::InvalidConfig("stealing must take at least $1".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use crate::config::GameConfig;
+    use crate::{Action, Coup, CoupBuilder};
+
+    #[test]
+    fn official_config_is_valid() {
+        for num_players in 2..=10 {
+            assert!(GameConfig::official(num_players).validate().is_ok());
+        }
+
+        assert_eq!(GameConfig::official(6).num_cards(), 15);
+        assert_eq!(GameConfig::official(8).num_cards(), 20);
+        assert_eq!(GameConfig::official(10).num_cards(), 25);
+    }
+
+    #[test]
+    fn rejects_impossible_configs() {
+        let mut config = GameConfig::official(4);
+        config.copies_per_character = 1;
+        assert!(config.validate().is_err());
+
+        let mut config = GameConfig::official(4);
+        config.forced_coup_threshold = 5;
+        assert!(config.validate().is_err());
+
+        let mut config = GameConfig::official(4);
+        config.copies_per_character = 9;
+        assert!(config.validate().is_err());
+
+        assert!(Coup::with_config(GameConfig::official(1), &mut thread_rng()).is_err());
+        assert!(Coup::new(11, &mut thread_rng()).is_err());
+
+        let mut config = GameConfig::two_player();
+        config.num_players = 3;
+        assert!(config.validate().is_err());
+
+        let mut config = GameConfig::official(4);
+        config.coin_cap = Some(6);
+        assert!(config.validate().is_err());
+
+        let mut config = GameConfig::official(4);
+        config.turn_limit = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn house_rules() {
+        let mut config = GameConfig::official(3);
+        config.starting_money = 5;
+        config.copies_per_character = 4;
+
+        let coup = Coup::with_config(config, &mut thread_rng()).unwrap();
+
+        assert_eq!(coup.players[0].money, 5);
+        assert_eq!(coup.deck.len(), 20 - 6);
+
+        // anything over the cap stays in the bank
+        let mut config = GameConfig::official(3);
+        config.coin_cap = Some(8);
+
+        let mut coup = CoupBuilder::with_config(config).coins(0, 7).build().unwrap();
+        for action in [Action::Tax(0), Action::Pass(1), Action::Pass(2), Action::Resolve(0)] {
+            coup = coup.apply_deterministic(action).unwrap();
+        }
+
+        assert_eq!(coup.players[0].money, 8);
+
+        // the reserve is full, so the conversion is paid to the bank
+        let mut config = GameConfig::reformation(3);
+        config.coin_cap = Some(8);
+
+        let coup = CoupBuilder::with_config(config).coins(0, 5).treasury_reserve(7).build().unwrap();
+        let coup = coup.apply_deterministic(Action::Convert(0, 1)).unwrap();
+        assert_eq!(coup.players[0].money, 3);
+        assert_eq!(coup.treasury_reserve, 8);
+    }
+}
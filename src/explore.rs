@@ -0,0 +1,380 @@
+// walks every position a small game can get to - every deal, every legal action, anyone still in
+// leaving and every card a chance node can turn up - so the turn state machine is checked everywhere, not just where tests
+// and random games happen to go
+// an exploration that finishes without a problem proves for that config that no reachable position
+// panics (so none of the unreachable!() branches behind actions() and apply_action() can be hit),
+// gets stuck or breaks an invariant
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use crate::{Action, Character, CompactCoup, Coup, CoupBuilder, CoupError, Departure, Faction, FuzzProblem, FuzzStep, GameConfig, SetupMode, State};
+use crate::fuzz::{apply, can_leave, guard};
+use crate::history::History;
+use crate::known_cards::KnownCards;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exploration {
+    // distinct positions, not counting the turn number, the history or the order of the deck
+    pub num_states: usize,
+    // how many positions are first reached after so many actions and draws from a deal
+    pub depths: Vec<usize>,
+    // finished games by who won
+    pub wins: Vec<usize>,
+    // finished games nobody won, see GameConfig::turn_limit
+    pub draws: usize,
+    // false if max_states was hit before everything was visited, in which case nothing is proven
+    pub complete: bool,
+}
+
+impl Display for Exploration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} states{}", self.num_states, if self.complete { "" } else { ", stopped at the limit" })?;
+        for (depth, &num_states) in self.depths.iter().enumerate() {
+            writeln!(f, "depth {depth}: {num_states}")?;
+        }
+        for (player_idx, &wins) in self.wins.iter().enumerate() {
+            writeln!(f, "player {player_idx} wins in {wins}")?;
+        }
+        writeln!(f, "draws: {}", self.draws)?;
+
+        Ok(())
+    }
+}
+
+// a position that went wrong, and the shortest way there from a deal
+#[derive(Clone, Debug)]
+pub struct ExploreFailure {
+    pub start: Box<Coup>,
+    pub steps: Vec<FuzzStep>,
+    pub problem: FuzzProblem,
+}
+
+impl Display for ExploreFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} after {} steps from", self.problem, self.steps.len())?;
+        writeln!(f, "{:?}", self.start)?;
+        for step in &self.steps {
+            writeln!(f, "{step}")?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Explorer {
+    config: GameConfig,
+    max_states: usize,
+}
+
+impl Explorer {
+    pub fn new(config: GameConfig) -> Result<Self, CoupError> {
+        config.validate()?;
+
+        Ok(Self {
+            config,
+            max_states: 1_000_000,
+        })
+    }
+
+    pub fn max_states(mut self, max_states: usize) -> Self {
+        self.max_states = max_states;
+        self
+    }
+
+    // breadth first from every deal, so the path to a problem is as short as it gets
+    pub fn explore(&self) -> Result<Exploration, ExploreFailure> {
+        let mut visited = Visited::default();
+        for deal in self.deals() {
+            visited.visit(deal, None, 0);
+        }
+
+        let mut wins = vec![0; self.config.num_players as usize];
+        let mut draws = 0;
+        let mut complete = true;
+
+        let mut state_idx = 0;
+        while state_idx < visited.states.len() {
+            if visited.states.len() >= self.max_states {
+                complete = false;
+                break;
+            }
+
+            let game = visited.game(state_idx);
+            let depth = visited.depths[state_idx];
+            let fail = |visited: &Visited, step: Option<FuzzStep>, problem: FuzzProblem| {
+                let (start, mut steps) = visited.path_to(state_idx);
+                steps.extend(step);
+                ExploreFailure { start: Box::new(start), steps, problem }
+            };
+
+            if let Err(err) = game.validate() {
+                return Err(fail(&visited, None, FuzzProblem::Invalid(err)));
+            }
+
+            let steps: Vec<FuzzStep> = if game.is_chance_node() {
+                game.chance_outcomes().into_iter().map(|(character, _)| FuzzStep::Draw(character)).collect()
+            } else if let Some(outcome) = game.outcome() {
+                match outcome.winner() {
+                    Some(winner) => wins[winner] += 1,
+                    None => draws += 1,
+                }
+                Vec::new()
+            } else {
+                let mut steps: Vec<FuzzStep> = match guard(|| game.actions()) {
+                    Ok(actions) => actions.into_iter().map(FuzzStep::Action).collect(),
+                    Err(problem) => return Err(fail(&visited, None, problem)),
+                };
+
+                // leaving isn't in actions(), and every departure leaves the game the same way
+                if can_leave(&game) {
+                    let living = game.players_indexes().filter(|&player_idx| !game.is_player_dead(player_idx));
+                    steps.extend(living.map(|player_idx| FuzzStep::Action(Action::Leave(player_idx, Departure::Concede))));
+                }
+
+                steps
+            };
+
+            if steps.is_empty() && !game.is_over() {
+                return Err(fail(&visited, None, FuzzProblem::Stuck));
+            }
+
+            for step in steps {
+                match guard(|| apply(&game, step)) {
+                    Ok(Ok(next)) => visited.visit(next, Some((state_idx, step)), depth + 1),
+                    Ok(Err(err)) => {
+                        let problem = match step {
+                            FuzzStep::Action(action) => FuzzProblem::Rejected(action, err),
+                            FuzzStep::Draw(_) => FuzzProblem::Invalid(err),
+                        };
+                        return Err(fail(&visited, None, problem));
+                    }
+                    Err(problem) => return Err(fail(&visited, Some(step), problem)),
+                }
+            }
+
+            state_idx += 1;
+        }
+
+        let mut depths = Vec::new();
+        for &depth in &visited.depths {
+            if depths.len() <= depth {
+                depths.resize(depth + 1, 0);
+            }
+            depths[depth] += 1;
+        }
+
+        Ok(Exploration {
+            num_states: visited.states.len(),
+            depths,
+            wins,
+            draws,
+            complete,
+        })
+    }
+
+    // every way the cards can be dealt, with the cards in each hand in either order
+    fn deals(&self) -> Vec<Coup> {
+        // two player hands are picked rather than dealt, so there's only one way to start
+        if self.config.setup == SetupMode::TwoPlayer {
+            let start = CoupBuilder::with_config(self.config).phase(State::AwaitingInfluenceSelection(0)).build();
+            return vec![start.expect("a valid config can always start picking influence")];
+        }
+
+        let mut unused: Vec<(Character, u8)> = self.config.characters().iter().map(|&character| (character, self.config.copies_per_character)).collect();
+        let mut deals = Vec::new();
+        self.deal(&mut unused, &mut Vec::new(), &mut deals);
+        deals
+    }
+
+    fn deal(&self, unused: &mut [(Character, u8)], hands: &mut Vec<(Character, Character)>, deals: &mut Vec<Coup>) {
+        if hands.len() == self.config.num_players as usize {
+            let builder = hands.iter()
+                .enumerate()
+                .fold(CoupBuilder::with_config(self.config), |builder, (player_idx, &(first, second))| builder.hand(player_idx, first, second));
+            deals.push(builder.build().expect("every deal from a valid config is a valid start"));
+            return;
+        }
+
+        for first in 0..unused.len() {
+            if unused[first].1 == 0 {
+                continue;
+            }
+            unused[first].1 -= 1;
+
+            for second in 0..unused.len() {
+                if unused[second].1 == 0 {
+                    continue;
+                }
+                unused[second].1 -= 1;
+
+                hands.push((unused[first].0, unused[second].0));
+                self.deal(unused, hands, deals);
+                hands.pop();
+
+                unused[second].1 += 1;
+            }
+
+            unused[first].1 += 1;
+        }
+    }
+}
+
+// positions are kept compact and looked up by key, since there can be millions of them
+#[derive(Default)]
+struct Visited {
+    states: Vec<CompactCoup>,
+    // the state each one was first reached from and the step taken, nothing for deals
+    parents: Vec<Option<(usize, FuzzStep)>>,
+    depths: Vec<usize>,
+    state_idxs: HashMap<u64, usize>,
+    // positions whose key was already taken by a different one
+    collisions: HashMap<CompactCoup, usize>,
+}
+
+impl Visited {
+    fn visit(&mut self, game: Coup, parent: Option<(usize, FuzzStep)>, depth: usize) {
+        let game = canonical(game);
+        let compact = CompactCoup::from(&game);
+
+        match self.state_idxs.get(&game.zobrist_key()) {
+            None => {
+                self.state_idxs.insert(game.zobrist_key(), self.states.len());
+            }
+            Some(&state_idx) if self.states[state_idx] == compact => return,
+            Some(_) if self.collisions.contains_key(&compact) => return,
+            Some(_) => {
+                self.collisions.insert(compact, self.states.len());
+            }
+        }
+
+        self.states.push(compact);
+        self.parents.push(parent);
+        self.depths.push(depth);
+    }
+
+    fn game(&self, state_idx: usize) -> Coup {
+        self.states[state_idx].unpack(History::disabled())
+    }
+
+    // the deal a state was first reached from, and the steps from there
+    fn path_to(&self, mut state_idx: usize) -> (Coup, Vec<FuzzStep>) {
+        let mut steps = Vec::new();
+        while let Some((parent_idx, step)) = self.parents[state_idx] {
+            steps.push(step);
+            state_idx = parent_idx;
+        }
+
+        steps.reverse();
+        (Coup::from(self.states[state_idx]), steps)
+    }
+}
+
+// evens out the parts of a game that don't change what can happen next, so positions that only
+// differ in them are explored once - the turn only matters when there's a limit on it
+fn canonical(mut game: Coup) -> Coup {
+    if game.config.turn_limit.is_none() {
+        game.turn = 0;
+        for elimination in game.eliminations.iter_mut() {
+            elimination.1 = 0;
+        }
+    }
+    game.history = History::disabled();
+    // what an inquisitor has been shown is only ever used for what players get to see
+    game.known_cards = KnownCards::default();
+    // two players can always target each other whatever their factions, and nobody comes back in
+    if game.config.reformation && game.players_indexes().filter(|&player_idx| !game.is_player_dead(player_idx)).count() <= 2 {
+        for player in game.players.iter_mut() {
+            player.faction = Some(Faction::Loyalist);
+        }
+    }
+    game.deck.sort_by_key(|&card| card as usize);
+    game.exchange_cards.sort_by_key(|&card| card as usize);
+    game.rehash();
+    game
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GameConfig, SetupMode};
+    use crate::explore::Explorer;
+
+    // as small as the rules go - one of each character, one card to exchange with, and a $3 coup
+    // with coins capped there so nobody saves up
+    fn tiny_config() -> GameConfig {
+        let mut config = GameConfig::official(2);
+        config.copies_per_character = 1;
+        config.exchange_draw = 1;
+        config.coup_cost = 3;
+        config.forced_coup_threshold = 3;
+        config.coin_cap = Some(3);
+        config
+    }
+
+    // the first turn of a tiny game is all the default test run has time for, with validate() checked
+    // after every step - the whole games are left for release builds, like the other rule sets
+    // cargo test --release explore -- --ignored
+    #[test]
+    fn explores_the_first_turn() {
+        let mut config = tiny_config();
+        config.turn_limit = Some(1);
+
+        let exploration = Explorer::new(config).unwrap().max_states(100_000).explore().unwrap_or_else(|failure| panic!("{failure}"));
+        assert!(exploration.complete);
+        assert_eq!(exploration.depths.iter().sum::<usize>(), exploration.num_states);
+        // 5 * 4 ways to deal the first hand and 3 * 2 for the second
+        assert_eq!(exploration.depths[0], 120);
+        assert!(exploration.draws > 0);
+        // nobody can lose both cards in the first turn, so the only way to win it is the other player leaving
+        assert!(exploration.wins.iter().all(|&wins| wins > 0));
+    }
+
+    #[test]
+    #[ignore]
+    fn explores_tiny_games() {
+        let exploration = Explorer::new(tiny_config()).unwrap().explore().unwrap_or_else(|failure| panic!("{failure}"));
+
+        assert!(exploration.complete);
+        assert_eq!(exploration.depths.iter().sum::<usize>(), exploration.num_states);
+        // 5 * 4 ways to deal the first hand and 3 * 2 for the second
+        assert_eq!(exploration.depths[0], 120);
+        assert!(exploration.wins.iter().all(|&wins| wins > 0));
+    }
+
+    #[test]
+    #[ignore]
+    fn explores_every_variant() {
+        let mut configs = [tiny_config(); 3];
+        configs[0].reformation = true;
+        configs[1].inquisitor = true;
+        configs[2].setup = SetupMode::TwoPlayer;
+        configs[2].copies_per_character = 2;
+
+        for config in configs {
+            let exploration = Explorer::new(config).unwrap().max_states(5_000_000).explore().unwrap_or_else(|failure| panic!("{failure}"));
+            println!("{config:?}\n{exploration}");
+
+            // the coin cap holds the treasury reserve down too, so every variant gets through everything
+            assert!(exploration.complete);
+            assert!(exploration.wins.iter().all(|&wins| wins > 0));
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn explores_up_to_the_turn_limit() {
+        let mut config = tiny_config();
+        config.turn_limit = Some(3);
+
+        let exploration = Explorer::new(config).unwrap().explore().unwrap_or_else(|failure| panic!("{failure}"));
+        assert!(exploration.complete);
+        assert!(exploration.draws > 0);
+        assert!(exploration.wins.iter().all(|&wins| wins > 0));
+    }
+
+    #[test]
+    fn stops_at_the_limit() {
+        let exploration = Explorer::new(tiny_config()).unwrap().max_states(1000).explore().unwrap();
+        assert!(!exploration.complete);
+        assert!(exploration.num_states < 1100);
+    }
+}
@@ -0,0 +1,375 @@
+// plays lots of random games across rule sets and checks every position they get to, so rules bugs
+// turn up here before they turn up at the table
+// a failing game is shrunk down to as few steps as still go wrong, and can be played back from its seed
+
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::mem::discriminant;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use crate::{Action, Character, Coup, CoupError, Departure, GameConfig, State};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FuzzStep {
+    Action(Action),
+    // the card that came off the deck at a chance node
+    Draw(Character),
+}
+
+impl Display for FuzzStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FuzzStep::Action(action) => write!(f, "{action}"),
+            FuzzStep::Draw(character) => write!(f, "draw:{character}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FuzzProblem {
+    // what the engine panicked with
+    Panic(String),
+    // the game isn't over, but nobody can do anything
+    Stuck,
+    // the game got somewhere the rules can't, see Coup::validate
+    Invalid(CoupError),
+    // an action the game listed as legal couldn't be applied
+    Rejected(Action, CoupError),
+    // the fuzzer's own check failed
+    Property(String),
+}
+
+impl Display for FuzzProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FuzzProblem::Panic(message) => write!(f, "panicked: {message}"),
+            FuzzProblem::Stuck => f.write_str("nobody can act in an unfinished game"),
+            FuzzProblem::Invalid(err) => write!(f, "{err}"),
+            FuzzProblem::Rejected(action, err) => write!(f, "legal action {action} was rejected: {err}"),
+            FuzzProblem::Property(reason) => write!(f, "check failed: {reason}"),
+        }
+    }
+}
+
+// a game that went wrong - dealing it with the seed and playing the steps gets back to the problem
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzFailure {
+    pub config: GameConfig,
+    pub seed: u64,
+    pub steps: Vec<FuzzStep>,
+    pub problem: FuzzProblem,
+}
+
+impl Display for FuzzFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} after {} steps from seed {}", self.problem, self.steps.len(), self.seed)?;
+        writeln!(f, "{:?}", self.config)?;
+        for step in &self.steps {
+            writeln!(f, "{step}")?;
+        }
+
+        Ok(())
+    }
+}
+
+// a check of the fuzzer's own to hold in every position, saying what's wrong if it doesn't
+pub type Property = fn(&Coup) -> Result<(), String>;
+
+pub struct Fuzzer {
+    configs: Vec<GameConfig>,
+    // per config
+    games: usize,
+    // games that go on longer than this are cut off
+    max_steps: usize,
+    seed: u64,
+    property: Option<Property>,
+}
+
+impl Fuzzer {
+    // every rule set at every table size it plays well at
+    pub fn new(seed: u64) -> Self {
+        let mut configs = vec![GameConfig::two_player(), GameConfig::official(10)];
+        for num_players in 2..=6 {
+            configs.extend([GameConfig::official(num_players), GameConfig::reformation(num_players), GameConfig::inquisitor(num_players)]);
+        }
+
+        Self {
+            configs,
+            games: 100,
+            max_steps: 1000,
+            seed,
+            property: None,
+        }
+    }
+
+    pub fn configs(mut self, configs: &[GameConfig]) -> Self {
+        self.configs = configs.to_vec();
+        self
+    }
+
+    pub fn games(mut self, games: usize) -> Self {
+        self.games = games;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    // an extra check for every position, on top of the ones the rules engine has to pass anyway
+    pub fn property(mut self, property: Property) -> Self {
+        self.property = Some(property);
+        self
+    }
+
+    // plays every game, returning how many positions were checked or the first failure, shrunk
+    pub fn run(&self) -> Result<usize, FuzzFailure> {
+        let mut positions = 0;
+
+        for (config_idx, &config) in self.configs.iter().enumerate() {
+            for game_idx in 0..self.games {
+                let seed = self.seed.wrapping_add((config_idx * self.games + game_idx) as u64);
+                positions += self.play(config, seed).map_err(|failure| self.shrink(failure))?;
+            }
+        }
+
+        Ok(positions)
+    }
+
+    // plays a failure's steps back, giving the failure they lead to now, if any
+    pub fn reproduce(&self, failure: &FuzzFailure) -> Option<FuzzFailure> {
+        self.replay(failure.config, failure.seed, &failure.steps)
+    }
+
+    fn play(&self, config: GameConfig, seed: u64) -> Result<usize, FuzzFailure> {
+        let fail = |steps: &[FuzzStep], problem| FuzzFailure { config, seed, steps: steps.to_vec(), problem };
+
+        let mut game = deal(config, seed).map_err(|problem| fail(&[], problem))?;
+        self.check(&game).map_err(|problem| fail(&[], problem))?;
+
+        // the same seed picks the moves, so a game is the same every time it's fuzzed
+        let mut rng = Pcg64::seed_from_u64(seed);
+        let mut steps = Vec::new();
+
+        while !game.is_over() && steps.len() < self.max_steps {
+            let next = guard(|| {
+                let step = match game.pending_draw() {
+                    Some(_) => FuzzStep::Draw(game.deck[rng.gen_range(0..game.deck.len())]),
+                    // now and then someone walks out, so the games that carry on without them get played too
+                    None if can_leave(&game) && rng.gen_ratio(1, 50) => {
+                        let living: Vec<usize> = game.players_indexes().filter(|&player_idx| !game.is_player_dead(player_idx)).collect();
+                        FuzzStep::Action(Action::Leave(living[rng.gen_range(0..living.len())], Departure::Concede))
+                    }
+                    None => FuzzStep::Action(game.random_action(&mut rng).expect("a checked game always has an action")),
+                };
+
+                (step, apply(&game, step))
+            });
+
+            let (step, result) = next.map_err(|problem| fail(&steps, problem))?;
+            steps.push(step);
+
+            game = match result {
+                Ok(game) => game,
+                Err(err) => return Err(fail(&steps, rejected(step, err))),
+            };
+
+            self.check(&game).map_err(|problem| fail(&steps, problem))?;
+        }
+
+        Ok(steps.len() + 1)
+    }
+
+    // like play, but with the steps given - any that can't be applied any more are skipped
+    fn replay(&self, config: GameConfig, seed: u64, steps: &[FuzzStep]) -> Option<FuzzFailure> {
+        let mut applied = Vec::new();
+        let fail = |steps: &[FuzzStep], problem| Some(FuzzFailure { config, seed, steps: steps.to_vec(), problem });
+
+        let mut game = match deal(config, seed) {
+            Ok(game) => game,
+            Err(problem) => return fail(&applied, problem),
+        };
+
+        if let Err(problem) = self.check(&game) {
+            return fail(&applied, problem);
+        }
+
+        for &step in steps {
+            match guard(|| apply(&game, step)) {
+                Ok(Ok(next)) => game = next,
+                Ok(Err(_)) => continue,
+                Err(problem) => {
+                    applied.push(step);
+                    return fail(&applied, problem);
+                }
+            }
+
+            applied.push(step);
+            if let Err(problem) = self.check(&game) {
+                return fail(&applied, problem);
+            }
+        }
+
+        None
+    }
+
+    // cuts steps out of a failing game for as long as it still fails the same way, first in big
+    // chunks and then one at a time, until no single step can go
+    fn shrink(&self, mut failure: FuzzFailure) -> FuzzFailure {
+        let mut chunk = failure.steps.len().max(1);
+
+        loop {
+            let mut shrunk = false;
+            let mut start = 0;
+
+            while start < failure.steps.len() {
+                let end = (start + chunk).min(failure.steps.len());
+                let candidate: Vec<FuzzStep> = failure.steps[..start].iter().chain(&failure.steps[end..]).copied().collect();
+
+                match self.replay(failure.config, failure.seed, &candidate) {
+                    Some(smaller) if discriminant(&smaller.problem) == discriminant(&failure.problem) && smaller.steps.len() < failure.steps.len() => {
+                        failure = smaller;
+                        shrunk = true;
+                    }
+                    _ => start += chunk,
+                }
+            }
+
+            if chunk == 1 && !shrunk {
+                return failure;
+            }
+
+            chunk = (chunk / 2).max(1);
+        }
+    }
+
+    fn check(&self, game: &Coup) -> Result<(), FuzzProblem> {
+        guard(|| {
+            game.validate().map_err(FuzzProblem::Invalid)?;
+
+            if let Some(property) = self.property {
+                property(game).map_err(FuzzProblem::Property)?;
+            }
+
+            if game.is_chance_node() {
+                return match game.chance_outcomes().is_empty() {
+                    true => Err(FuzzProblem::Stuck),
+                    false => Ok(()),
+                };
+            }
+
+            // the game stops taking actions once it's won or drawn
+            if game.is_over() {
+                return Ok(());
+            }
+
+            let actions = game.actions();
+            if actions.is_empty() {
+                return Err(FuzzProblem::Stuck);
+            }
+
+            for action in actions {
+                game.apply_deterministic(action).map_err(|err| FuzzProblem::Rejected(action, err))?;
+            }
+
+            // leaving isn't in actions(), since anyone can do it whenever they like
+            if can_leave(game) {
+                for player_idx in game.players_indexes().filter(|&player_idx| !game.is_player_dead(player_idx)) {
+                    let action = Action::Leave(player_idx, Departure::Concede);
+                    game.apply_deterministic(action).map_err(|err| FuzzProblem::Rejected(action, err))?;
+                }
+            }
+
+            Ok(())
+        }).and_then(|checked| checked)
+    }
+}
+
+// nobody has a hand to leave with until everyone has picked
+pub(crate) fn can_leave(game: &Coup) -> bool {
+    !matches!(game.state, State::AwaitingInfluenceSelection(_))
+}
+
+fn deal(config: GameConfig, seed: u64) -> Result<Coup, FuzzProblem> {
+    guard(|| Coup::with_config(config, &mut Pcg64::seed_from_u64(seed)))?
+        .map_err(FuzzProblem::Invalid)
+}
+
+pub(crate) fn apply(game: &Coup, step: FuzzStep) -> Result<Coup, CoupError> {
+    match step {
+        FuzzStep::Action(action) => game.apply_deterministic(action),
+        FuzzStep::Draw(character) => game.apply_chance(character),
+    }
+}
+
+// a step the fuzzer picked itself should always apply
+fn rejected(step: FuzzStep, err: CoupError) -> FuzzProblem {
+    match step {
+        FuzzStep::Action(action) => FuzzProblem::Rejected(action, err),
+        FuzzStep::Draw(_) => FuzzProblem::Invalid(err),
+    }
+}
+
+// runs f, turning a panic into a problem instead of taking the fuzzer down with it
+pub(crate) fn guard<T>(f: impl FnOnce() -> T) -> Result<T, FuzzProblem> {
+    catch_unwind(AssertUnwindSafe(f)).map_err(|payload| FuzzProblem::Panic(panic_message(payload)))
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Coup, GameConfig};
+    use crate::fuzz::{FuzzFailure, FuzzProblem, Fuzzer};
+
+    fn nobody_gets_rich(game: &Coup) -> Result<(), String> {
+        match game.players_indexes().find(|&player_idx| game.coins(player_idx) > 4) {
+            Some(player_idx) => Err(format!("player {player_idx} has ${}", game.coins(player_idx))),
+            None => Ok(()),
+        }
+    }
+
+    #[test]
+    fn random_games_hold_up() {
+        let positions = Fuzzer::new(1).games(2).run().unwrap();
+        assert!(positions > 1000);
+    }
+
+    #[test]
+    fn shrinks_failures() {
+        let fuzzer = Fuzzer::new(7).configs(&[GameConfig::official(3)]).games(1).property(nobody_gets_rich);
+        let failure = fuzzer.run().unwrap_err();
+        assert!(matches!(failure.problem, FuzzProblem::Property(_)));
+
+        // the shrunk game still fails, and doesn't without any one of its steps
+        assert_eq!(fuzzer.reproduce(&failure), Some(failure.clone()));
+        assert!(!failure.steps.is_empty());
+        for step_idx in 0..failure.steps.len() {
+            let mut steps = failure.steps.clone();
+            steps.remove(step_idx);
+            assert!(!matches!(fuzzer.replay(failure.config, failure.seed, &steps), Some(FuzzFailure { problem: FuzzProblem::Property(_), .. })));
+        }
+
+    }
+
+    // a long run for hunting bugs, rather than checking on every change
+    // cargo test --release fuzz_many_games -- --ignored
+    #[test]
+    #[ignore]
+    fn fuzz_many_games() {
+        match Fuzzer::new(0).games(2000).run() {
+            Ok(positions) => println!("checked {positions} positions"),
+            Err(failure) => panic!("{failure}"),
+        }
+    }
+}
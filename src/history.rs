@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use crate::{Action, Character, Coup, Faction, Undo};
+use crate::undo::Change;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visibility {
+    Public,
+    // only this player gets to know about it
+    Private(usize),
+}
+
+// where coins move from and to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Purse {
+    Bank,
+    Player(usize),
+    TreasuryReserve,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventKind {
+    Action(Action),
+    // shown to the table to win a challenge, before being shuffled back
+    CardRevealed(usize, Character),
+    // influence lost for good
+    CardLost(usize, Character),
+    // (player, number of cards) put back into the deck
+    CardsShuffledBack(usize, usize),
+    CardDrawn(usize, Character),
+    // (examined player, card index, character) shown to the inquisitor
+    CardShown(usize, usize, Character),
+    CoinsMoved(Purse, Purse, u8),
+    FactionChanged(usize, Faction),
+    PlayerEliminated(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub turn: usize,
+    pub visibility: Visibility,
+    pub kind: EventKind,
+}
+
+impl Event {
+    pub fn is_visible_to(&self, player_idx: usize) -> bool {
+        match self.visibility {
+            Visibility::Public => true,
+            Visibility::Private(owner_idx) => owner_idx == player_idx,
+        }
+    }
+}
+
+// the log of how a game got to where it is - it isn't part of the position itself, so Coup leaves
+// it out when comparing games
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct History {
+    events: Vec<Event>,
+    // compact games are played without a history
+    #[serde(skip)]
+    disabled: bool,
+}
+
+impl History {
+    pub(crate) fn disabled() -> Self {
+        Self {
+            events: Vec::new(),
+            disabled: true,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.events.truncate(len);
+    }
+}
+
+impl Coup {
+    // every event, including the private ones
+    pub fn history(&self) -> &[Event] {
+        &self.history.events
+    }
+
+    pub fn history_visible_to(&self, player_idx: usize) -> impl Iterator<Item=&Event> + '_ {
+        self.history.events.iter().filter(move |event| event.is_visible_to(player_idx))
+    }
+
+    pub(crate) fn record(&mut self, visibility: Visibility, kind: EventKind) {
+        if self.history.disabled {
+            return;
+        }
+
+        self.history.events.push(Event {
+            turn: self.turn,
+            visibility,
+            kind,
+        });
+    }
+
+    pub(crate) fn record_action(&mut self, action: &Action) {
+        // picks made from a player's own hand are theirs to know
+        let visibility = match *action {
+            Action::Keep(player_idx, _, _) | Action::ChooseInfluence(player_idx, _) => Visibility::Private(player_idx),
+            _ => Visibility::Public,
+        };
+
+        self.record(visibility, EventKind::Action(*action));
+    }
+
+    // gives how much was moved
+    pub(crate) fn move_coins(&mut self, from: Purse, to: Purse, amount: u8, undo: &mut Undo) -> u8 {
+        // purses can only hold so much, whatever wouldn't fit stays where it came from
+        let cap = self.config.coin_cap.unwrap_or(u8::MAX);
+        let amount = match to {
+            Purse::Bank => amount,
+            Purse::Player(player_idx) => amount.min(cap.saturating_sub(self.players[player_idx].money)),
+            Purse::TreasuryReserve => amount.min(cap.saturating_sub(self.treasury_reserve)),
+        };
+
+        if amount == 0 {
+            return 0;
+        }
+
+        match from {
+            Purse::Bank => {}
+            Purse::Player(player_idx) => {
+                undo.push(Change::Money(player_idx, self.players[player_idx].money));
+                self.players[player_idx].money -= amount;
+            }
+            Purse::TreasuryReserve => self.treasury_reserve -= amount,
+        }
+
+        match to {
+            Purse::Bank => {}
+            Purse::Player(player_idx) => {
+                undo.push(Change::Money(player_idx, self.players[player_idx].money));
+                self.players[player_idx].money += amount;
+            }
+            Purse::TreasuryReserve => self.treasury_reserve += amount,
+        }
+
+        self.record(Visibility::Public, EventKind::CoinsMoved(from, to, amount));
+        amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use crate::{Action, Coup, CoupBuilder, GameConfig};
+    use crate::Character::{Captain, Duke};
+    use crate::history::{EventKind, History, Purse, Visibility};
+
+    #[test]
+    fn records_events() {
+        let mut rng = thread_rng();
+        let mut coup = CoupBuilder::new(3).hand(0, Captain, Captain).hand(1, Duke, Captain).build().unwrap();
+
+        coup = coup.apply_action(Action::Income(0), &mut rng).unwrap();
+        coup = coup.apply_action(Action::Tax(1), &mut rng).unwrap();
+        coup = coup.apply_action(Action::Challenge(2), &mut rng).unwrap();
+        coup = coup.apply_action(Action::Reveal(1, 0), &mut rng).unwrap();
+
+        let kinds: Vec<&EventKind> = coup.history().iter().map(|event| &event.kind).collect();
+        assert_eq!(kinds[0], &EventKind::Action(Action::Income(0)));
+        assert_eq!(kinds[1], &EventKind::CoinsMoved(Purse::Bank, Purse::Player(0), 1));
+        assert_eq!(kinds[4], &EventKind::Action(Action::Reveal(1, 0)));
+        assert_eq!(kinds[5], &EventKind::CardRevealed(1, Duke));
+        assert_eq!(kinds[6], &EventKind::CardsShuffledBack(1, 1));
+        assert!(matches!(kinds[7], EventKind::CardDrawn(1, _)));
+
+        // turn numbers follow the game
+        assert_eq!(coup.history()[0].turn, 0);
+        assert_eq!(coup.history()[2].turn, 1);
+
+        // only p1 knows what they drew
+        assert_eq!(coup.history()[7].visibility, Visibility::Private(1));
+        assert_eq!(coup.history_visible_to(1).count(), 8);
+        assert_eq!(coup.history_visible_to(2).count(), 7);
+
+        let json = serde_json::to_string(&coup).unwrap();
+        let loaded: Coup = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.history, coup.history);
+
+        // a game compares by its position, whatever its history
+        let mut forgetful = coup.clone();
+        forgetful.history = History::default();
+        assert_ne!(forgetful.history, coup.history);
+        assert_eq!(forgetful, coup);
+    }
+
+    #[test]
+    fn coins_never_overflow() {
+        let mut rng = thread_rng();
+        let mut config = GameConfig::official(3);
+        config.forced_coup_threshold = u8::MAX;
+
+        // tax would take p0 past what a purse can hold, so the rest stays in the bank
+        let mut coup = CoupBuilder::with_config(config).coins(0, 254).build().unwrap();
+        for action in [Action::Tax(0), Action::Pass(1), Action::Pass(2), Action::Resolve(0)] {
+            coup = coup.apply_action(action, &mut rng).unwrap();
+        }
+
+        assert_eq!(coup.coins(0), u8::MAX);
+        assert_eq!(coup.history().last().unwrap().kind, EventKind::CoinsMoved(Purse::Bank, Purse::Player(0), 1));
+    }
+
+    #[test]
+    fn records_eliminations() {
+        let mut rng = thread_rng();
+        let mut coup = Coup::new(3, &mut rng).unwrap();
+        coup.players[0].money = 7;
+        coup.players[1].influence_cards[1].1 = true;
+        let lost = coup.players[1].influence_cards[0].0;
+
+        coup = coup.apply_action(Action::Coup(0, 1), &mut rng).unwrap();
+        coup = coup.apply_action(Action::Lose(1, 0), &mut rng).unwrap();
+
+        let kinds: Vec<&EventKind> = coup.history().iter().map(|event| &event.kind).collect();
+        assert_eq!(kinds[1], &EventKind::CoinsMoved(Purse::Player(0), Purse::Bank, 7));
+        assert_eq!(kinds[3], &EventKind::CardLost(1, lost));
+        assert_eq!(kinds[4], &EventKind::PlayerEliminated(1));
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// a vec with a fixed capacity that lives inline, so copying it never allocates
+// slots past the length hold leftovers and are never looked at
+#[derive(Copy, Clone)]
+pub struct InlineVec<T: Copy + Default, const N: usize> {
+    len: u8,
+    items: [T; N],
+}
+
+impl<T: Copy + Default, const N: usize> InlineVec<T, N> {
+    pub fn new() -> Self {
+        Self {
+            len: 0,
+            items: [T::default(); N],
+        }
+    }
+
+    pub fn from_slice(items: &[T]) -> Self {
+        let mut inline_vec = Self::new();
+        inline_vec.extend_from_slice(items);
+        inline_vec
+    }
+
+    pub fn push(&mut self, item: T) {
+        assert!((self.len as usize) < N, "inline vec is full at {N} items");
+        self.items[self.len as usize] = item;
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        Some(self.items[self.len as usize])
+    }
+
+    pub fn insert(&mut self, idx: usize, item: T) {
+        assert!(idx <= self.len(), "insert index {idx} is past the end");
+        self.push(item);
+        self[idx..].rotate_right(1);
+    }
+
+    pub fn remove(&mut self, idx: usize) -> T {
+        let item = self[idx];
+        self[idx..].rotate_left(1);
+        self.len -= 1;
+        item
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len as u8);
+    }
+
+    pub fn extend_from_slice(&mut self, items: &[T]) {
+        for &item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Default for InlineVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy + Default, const N: usize> Deref for InlineVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items[..self.len as usize]
+    }
+}
+
+impl<T: Copy + Default, const N: usize> DerefMut for InlineVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.items[..self.len as usize]
+    }
+}
+
+impl<T: Copy + Default, const N: usize> FromIterator<T> for InlineVec<T, N> {
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> Self {
+        let mut inline_vec = Self::new();
+        for item in iter {
+            inline_vec.push(item);
+        }
+        inline_vec
+    }
+}
+
+impl<'a, T: Copy + Default, const N: usize> IntoIterator for &'a InlineVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Copy + Default + PartialEq, const N: usize> PartialEq for InlineVec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Copy + Default + Eq, const N: usize> Eq for InlineVec<T, N> {}
+
+impl<T: Copy + Default + Hash, const N: usize> Hash for InlineVec<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+impl<T: Copy + Default + Debug, const N: usize> Debug for InlineVec<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// same format as a vec
+impl<T: Copy + Default + Serialize, const N: usize> Serialize for InlineVec<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<'de, T: Copy + Default + Deserialize<'de>, const N: usize> Deserialize<'de> for InlineVec<T, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = Vec::<T>::deserialize(deserializer)?;
+        if items.len() > N {
+            return Err(serde::de::Error::invalid_length(items.len(), &format!("at most {N} items").as_str()));
+        }
+
+        Ok(Self::from_slice(&items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inline_vec::InlineVec;
+
+    #[test]
+    fn behaves_like_a_vec() {
+        let mut inline_vec: InlineVec<u8, 4> = InlineVec::from_slice(&[1, 2, 3]);
+        let mut vec = vec![1, 2, 3];
+
+        inline_vec.insert(1, 9);
+        vec.insert(1, 9);
+        assert_eq!(*inline_vec, *vec);
+
+        assert_eq!(inline_vec.remove(0), vec.remove(0));
+        assert_eq!(inline_vec.pop(), vec.pop());
+        assert_eq!(*inline_vec, *vec);
+
+        // leftovers past the length don't count
+        let other: InlineVec<u8, 4> = InlineVec::from_slice(&[9, 2]);
+        assert_eq!(inline_vec, other);
+
+        inline_vec.truncate(0);
+        assert!(inline_vec.is_empty());
+
+        let json = serde_json::to_string(&other).unwrap();
+        assert_eq!(json, "[9,2]");
+        assert_eq!(serde_json::from_str::<InlineVec<u8, 4>>(&json).unwrap(), other);
+        assert!(serde_json::from_str::<InlineVec<u8, 4>>("[1,2,3,4,5]").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::config::MAX_PLAYERS;
+
+// cards an inquisitor has been shown and let the owner keep, as one bit per (owner, card index)
+// for each observer - fixed size, so copying it never allocates
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) struct KnownCards([u32; MAX_PLAYERS as usize]);
+
+impl KnownCards {
+    fn bit(owner_idx: usize, card_idx: usize) -> u32 {
+        1 << (owner_idx * 2 + card_idx)
+    }
+
+    pub fn contains(&self, observer_idx: usize, owner_idx: usize, card_idx: usize) -> bool {
+        self.0[observer_idx] & Self::bit(owner_idx, card_idx) != 0
+    }
+
+    // true if the card wasn't already known
+    pub fn insert(&mut self, observer_idx: usize, owner_idx: usize, card_idx: usize) -> bool {
+        let known = self.contains(observer_idx, owner_idx, card_idx);
+        self.0[observer_idx] |= Self::bit(owner_idx, card_idx);
+        !known
+    }
+
+    // nobody knows the card anymore, true if anybody did
+    pub fn forget(&mut self, owner_idx: usize, card_idx: usize) -> bool {
+        let bit = Self::bit(owner_idx, card_idx);
+        let known = self.0.iter().any(|&known| known & bit != 0);
+        for known in &mut self.0 {
+            *known &= !bit;
+        }
+        known
+    }
+
+    // (observer, owner, card index) in sorted order
+    pub fn iter(&self) -> impl Iterator<Item=(usize, usize, usize)> + '_ {
+        self.0.iter().enumerate().flat_map(|(observer_idx, &known)| {
+            (0..u32::BITS as usize)
+                .filter(move |&bit| known & (1 << bit) != 0)
+                .map(move |bit| (observer_idx, bit / 2, bit % 2))
+        })
+    }
+}
+
+// same format as the sorted list of (observer, owner, card index) it used to be
+impl Serialize for KnownCards {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for KnownCards {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut known_cards = Self::default();
+        for (observer_idx, owner_idx, card_idx) in Vec::<(usize, usize, usize)>::deserialize(deserializer)? {
+            if observer_idx >= MAX_PLAYERS as usize || owner_idx >= MAX_PLAYERS as usize || card_idx >= 2 {
+                return Err(serde::de::Error::custom(format!("player {observer_idx} can't know card {card_idx} of player {owner_idx}")));
+            }
+
+            known_cards.insert(observer_idx, owner_idx, card_idx);
+        }
+
+        Ok(known_cards)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::known_cards::KnownCards;
+
+    #[test]
+    fn behaves_like_a_sorted_list() {
+        let mut known_cards = KnownCards::default();
+        assert!(known_cards.insert(2, 0, 1));
+        assert!(known_cards.insert(0, 9, 0));
+        assert!(known_cards.insert(0, 1, 1));
+        assert!(!known_cards.insert(0, 1, 1));
+        assert!(known_cards.insert(3, 1, 1));
+
+        assert_eq!(known_cards.iter().collect::<Vec<_>>(), vec![(0, 1, 1), (0, 9, 0), (2, 0, 1), (3, 1, 1)]);
+        assert!(known_cards.contains(2, 0, 1));
+        assert!(!known_cards.contains(2, 0, 0));
+
+        // a swapped out card is forgotten by everyone
+        assert!(known_cards.forget(1, 1));
+        assert!(!known_cards.forget(1, 1));
+        assert_eq!(known_cards.iter().collect::<Vec<_>>(), vec![(0, 9, 0), (2, 0, 1)]);
+
+        let json = serde_json::to_string(&known_cards).unwrap();
+        assert_eq!(json, "[[0,9,0],[2,0,1]]");
+        assert_eq!(serde_json::from_str::<KnownCards>(&json).unwrap(), known_cards);
+        assert!(serde_json::from_str::<KnownCards>("[[0,1,2]]").is_err());
+    }
+}
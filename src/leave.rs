@@ -0,0 +1,248 @@
+// players leaving part way through - conceding, timing out or being removed by the server
+// their whole hand is revealed so they're out like anyone knocked out, and whatever the turn was
+// waiting on from them is settled so the game carries on
+// - the current player leaving ends their turn, and whatever was left of it is dropped
+// - a responder who hadn't passed yet no longer counts toward the passes a proposal needs
+// - a block falls through when the blocker leaves, so the proposal goes ahead
+// - a challenge is dropped when the challenger leaves, so a challenged block stands and a
+//   challenged proposal goes ahead
+// - a player who owed a card has lost it, and the turn goes on as if they'd picked
+// - an examination ends when the examined player leaves
+
+use crate::{Coup, EventKind, LeaverCoins, Purse, State, Undo, Visibility};
+use crate::undo::Change;
+
+impl Coup {
+    pub(crate) fn leave(&mut self, player_idx: usize, undo: &mut Undo) {
+        for card_idx in self.player_active_influence_cards(player_idx).collect::<Vec<usize>>() {
+            self.lose_influence_card(player_idx, card_idx, undo);
+        }
+
+        if self.config.leaver_coins == LeaverCoins::Returned {
+            self.move_coins(Purse::Player(player_idx), Purse::Bank, self.players[player_idx].money, undo);
+        }
+
+        if player_idx == self.current_player_idx {
+            self.return_exchange_cards(player_idx, undo);
+            self.go_next_turn();
+            return;
+        }
+
+        match self.state {
+            State::AwaitingProposalResponse(num_remaining_passers) if self.priority_player_idx == Some(player_idx) => {
+                if num_remaining_passers == 1 {
+                    self.state = State::ResolveProposal;
+                    self.priority_player_idx = Some(self.current_player_idx);
+                } else {
+                    self.state = State::AwaitingProposalResponse(num_remaining_passers - 1);
+                    self.go_next_prio();
+                }
+            }
+            State::AwaitingProposalResponse(num_remaining_passers) if !self.has_passed(player_idx) => {
+                self.state = State::AwaitingProposalResponse(num_remaining_passers - 1);
+            }
+            State::AwaitingProposalBlockResponse(blocker_player_idx) |
+            State::AwaitingChallengedBlockResponse(blocker_player_idx, _) if blocker_player_idx == player_idx => {
+                self.proposal_blocked_with = None;
+                self.state = State::ResolveProposal;
+                self.priority_player_idx = Some(self.current_player_idx);
+            }
+            State::AwaitingProposalBlockResponse(_) if self.priority_player_idx == Some(player_idx) => {
+                self.go_next_prio();
+            }
+            State::AwaitingChallengedBlockResponse(_, challenger_player_idx) if challenger_player_idx == player_idx => {
+                self.go_next_turn();
+            }
+            State::AwaitingChallengedProposalResponse(challenger_player_idx) if challenger_player_idx == player_idx => {
+                self.state = State::ResolveProposal;
+            }
+            State::AwaitingLoseInfluence(loser_player_idx, end_turn) if loser_player_idx == player_idx => {
+                self.state = State::ResolveProposal;
+                self.priority_player_idx = Some(self.current_player_idx);
+
+                if end_turn {
+                    self.go_next_turn();
+                }
+            }
+            State::AwaitingExamineShow(examined_player_idx) |
+            State::AwaitingExamineDecision(examined_player_idx, _) if examined_player_idx == player_idx => {
+                self.go_next_turn();
+            }
+            _ => {}
+        }
+    }
+
+    // responders get priority in seat order after the current player, so everyone between the
+    // current player and whoever has priority now has already passed
+    fn has_passed(&self, player_idx: usize) -> bool {
+        let priority_player_idx = self.priority_player_idx.expect("responders always have someone with priority");
+        let seats_after_current = |idx: usize| (idx + self.players.len() - self.current_player_idx) % self.players.len();
+
+        seats_after_current(player_idx) < seats_after_current(priority_player_idx)
+    }
+
+    // cards drawn for an exchange that's never going to be finished go back in the deck
+    fn return_exchange_cards(&mut self, player_idx: usize, undo: &mut Undo) {
+        if self.exchange_cards.is_empty() {
+            return;
+        }
+
+        let exchange_cards = std::mem::take(&mut self.exchange_cards);
+        undo.push(Change::ExchangeCardsCleared(exchange_cards));
+
+        self.record(Visibility::Public, EventKind::CardsShuffledBack(player_idx, exchange_cards.len()));
+        undo.push(Change::DeckPushed(exchange_cards.len()));
+        self.deck.extend_from_slice(&exchange_cards);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Action, Coup, CoupBuilder, CoupError, Departure, GameConfig, LeaverCoins, State};
+    use crate::Action::{Challenge, Pass, Resolve};
+    use crate::Character::{Ambassador, Captain, Duke};
+
+    fn play(mut coup: Coup, actions: &[Action]) -> Coup {
+        for &action in actions {
+            coup = coup.apply_deterministic(action).unwrap_or_else(|err| panic!("{action}: {err}"));
+        }
+        coup
+    }
+
+    fn leave(player_idx: usize) -> Action {
+        Action::Leave(player_idx, Departure::Concede)
+    }
+
+    #[test]
+    fn current_player_leaving_ends_their_turn() {
+        let coup = CoupBuilder::new(3).hand(0, Ambassador, Duke).build().unwrap();
+        let mut coup = play(coup, &[Action::Exchange(0), Pass(1), Pass(2), Resolve(0)]);
+        while coup.is_chance_node() {
+            coup = coup.apply_chance(coup.deck[0]).unwrap();
+        }
+
+        assert_eq!(coup.state, State::AwaitingExchangeChoice);
+        assert_eq!(coup.apply_deterministic(leave(0)).unwrap().exchange_cards.len(), 0);
+
+        let coup = play(coup, &[leave(0)]);
+        assert_eq!(coup.deck.len(), 9);
+        assert_eq!(coup.state, State::AwaitingProposal);
+        assert_eq!(coup.current_player_idx, 1);
+        assert_eq!(coup.coins(0), 0);
+        assert_eq!(coup.eliminations(), &[(0, 0)]);
+    }
+
+    #[test]
+    fn responders_who_leave_stop_counting() {
+        let coup = play(CoupBuilder::new(4).build().unwrap(), &[Action::Tax(0), Pass(1)]);
+        assert_eq!(coup.state, State::AwaitingProposalResponse(2));
+
+        // already passed
+        let coup = play(coup, &[leave(1)]);
+        assert_eq!((coup.state, coup.priority_player_idx), (State::AwaitingProposalResponse(2), Some(2)));
+
+        // has priority
+        let coup = play(coup, &[leave(2)]);
+        assert_eq!((coup.state, coup.priority_player_idx), (State::AwaitingProposalResponse(1), Some(3)));
+
+        let coup = play(coup, &[Pass(3), Resolve(0)]);
+        assert_eq!(coup.coins(0), 5);
+
+        // still to respond
+        let coup = play(CoupBuilder::new(4).build().unwrap(), &[Action::Tax(0), leave(3)]);
+        assert_eq!((coup.state, coup.priority_player_idx), (State::AwaitingProposalResponse(2), Some(1)));
+        let coup = play(coup, &[Pass(1), Pass(2)]);
+        assert_eq!(coup.state, State::ResolveProposal);
+    }
+
+    #[test]
+    fn blocks_fall_through_and_challenges_drop() {
+        let start = CoupBuilder::new(3).hand(1, Duke, Duke).build().unwrap();
+        let blocked = play(start.clone(), &[Action::ForeignAid(0), Action::Block(1, Duke)]);
+
+        // the blocker leaves, so the foreign aid goes through
+        let coup = play(blocked.clone(), &[leave(1)]);
+        assert_eq!((coup.state, coup.proposal_blocked_with), (State::ResolveProposal, None));
+        assert_eq!(play(coup, &[Resolve(0)]).coins(0), 4);
+
+        // the blocker leaves while their block is challenged
+        let coup = play(blocked.clone(), &[Challenge(2), leave(1)]);
+        assert_eq!((coup.state, coup.proposal_blocked_with), (State::ResolveProposal, None));
+
+        // the challenger leaves, so the block stands
+        let coup = play(blocked.clone(), &[Challenge(2), leave(2)]);
+        assert_eq!((coup.state, coup.current_player_idx, coup.coins(0)), (State::AwaitingProposal, 1, 2));
+
+        // the only other responder leaves, so it's back to the blocked player
+        let coup = play(blocked, &[leave(2)]);
+        assert_eq!((coup.state, coup.priority_player_idx), (State::AwaitingProposalBlockResponse(1), Some(0)));
+
+        // the challenger of a proposal leaves, so it goes ahead
+        let coup = play(start, &[Action::Tax(0), Challenge(1), leave(1)]);
+        assert_eq!((coup.state, coup.priority_player_idx), (State::ResolveProposal, Some(0)));
+    }
+
+    #[test]
+    fn leaving_instead_of_losing_a_card() {
+        let coup = CoupBuilder::new(3).coins(0, 7).build().unwrap();
+        let coup = play(coup, &[Action::Coup(0, 1), leave(1)]);
+        assert_eq!((coup.state, coup.current_player_idx), (State::AwaitingProposal, 2));
+        assert!(coup.is_player_dead(1));
+
+        let coup = CoupBuilder::new(3).hand(0, Captain, Duke).hand(2, Captain, Captain).build().unwrap();
+        let mut coup = play(coup, &[Action::Steal(0, 2), Pass(1), Challenge(2), Action::Reveal(0, 0)]);
+        while coup.is_chance_node() {
+            coup = coup.apply_chance(coup.deck[0]).unwrap();
+        }
+
+        let coup = play(coup, &[leave(2)]);
+        assert_eq!((coup.state, coup.priority_player_idx), (State::ResolveProposal, Some(0)));
+        assert_eq!(play(coup, &[Resolve(0)]).coins(0), 2);
+    }
+
+    #[test]
+    fn coins_follow_the_config() {
+        let mut config = GameConfig::official(2);
+        config.leaver_coins = LeaverCoins::Kept;
+
+        let coup = CoupBuilder::with_config(config).coins(1, 5).build().unwrap();
+        let coup = play(coup, &[Action::Leave(1, Departure::Timeout)]);
+        assert_eq!(coup.coins(1), 5);
+        assert_eq!(coup.winner(), Some(0));
+        assert_eq!(coup.apply_deterministic(leave(0)).unwrap_err(), CoupError::GameOver);
+    }
+
+    #[test]
+    fn kept_coins_cant_be_stolen() {
+        let mut config = GameConfig::official(3);
+        config.leaver_coins = LeaverCoins::Kept;
+
+        let coup = CoupBuilder::with_config(config).coins(2, 5).build().unwrap();
+        let coup = play(coup, &[Action::Steal(0, 2), Pass(1), leave(2), Resolve(0)]);
+        assert!(coup.is_player_dead(2));
+        assert_eq!((coup.coins(0), coup.coins(2)), (2, 5));
+        assert_eq!(coup.current_player_idx, 1);
+    }
+
+    #[test]
+    fn rejects_leaving_twice_or_before_the_game_starts() {
+        let coup = play(CoupBuilder::new(3).build().unwrap(), &[leave(2)]);
+        assert_eq!(coup.apply_deterministic(leave(2)).unwrap_err(), CoupError::DeadTarget(2));
+        assert_eq!(coup.apply_deterministic(leave(5)).unwrap_err(), CoupError::NoSuchPlayer(5));
+
+        let picking = CoupBuilder::with_config(GameConfig::two_player()).phase(State::AwaitingInfluenceSelection(0)).build().unwrap();
+        assert_eq!(picking.apply_deterministic(leave(1)).unwrap_err(), CoupError::WrongPhase(leave(1)));
+    }
+
+    #[test]
+    fn leaving_can_be_undone() {
+        let mut coup = play(CoupBuilder::new(3).build().unwrap(), &[Action::Tax(0), Challenge(1)]);
+        let before = coup.clone();
+
+        let undo = coup.apply_deterministic_mut(leave(0)).unwrap();
+        assert_ne!(coup, before);
+        coup.undo(undo);
+        assert_eq!(coup, before);
+        assert_eq!(coup.zobrist_key(), before.zobrist_key());
+    }
+}
@@ -1,19 +1,56 @@
 pub mod action;
 pub mod ai;
+pub mod builder;
+pub mod chance;
+pub mod compact;
+pub mod config;
+pub mod explore;
+pub mod fuzz;
+pub mod history;
+mod inline_vec;
+mod known_cards;
+pub mod leave;
+pub mod notation;
+pub mod outcome;
+pub mod record;
+pub mod scenario;
+pub mod undo;
+pub mod validate;
+pub mod view;
+pub mod zobrist;
 
 pub use ai::generate_graph;
 pub use ai::GraphNode;
-pub use action::Action;
-
-use std::fmt::{Debug, Formatter};
-use std::ops::{Deref, Range};
+pub use action::{Action, Departure};
+pub use builder::CoupBuilder;
+pub use chance::Draw;
+pub use compact::CompactCoup;
+pub use config::{GameConfig, LeaverCoins, SetupMode};
+pub use explore::{Exploration, ExploreFailure, Explorer};
+pub use fuzz::{FuzzFailure, FuzzProblem, FuzzStep, Fuzzer};
+pub use history::{Event, EventKind, Purse, Visibility};
+pub use outcome::GameOutcome;
+pub use record::{GameRecord, GameResult, RecordedMove};
+pub use scenario::{Scenario, run_scenarios};
+pub use undo::Undo;
+pub use view::PlayerView;
+
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use rand::seq::SliceRandom;
 use rand::{Rng};
 use serde::{Deserialize, Serialize};
-use crate::Character::{Ambassador, Assassin, Captain, Contessa, Duke};
-
-#[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
-enum State {
+use crate::Character::{Assassin, Captain, Contessa, Duke, Inquisitor};
+use crate::config::{MAX_CARDS, MAX_EXCHANGE_DRAW, MAX_PLAYERS};
+use crate::history::History;
+use crate::inline_vec::InlineVec;
+use crate::known_cards::KnownCards;
+use crate::undo::Change;
+use crate::zobrist::Zobrist;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum State {
     AwaitingProposal,
     // num passes remaining
     AwaitingProposalResponse(usize),
@@ -25,49 +62,203 @@ enum State {
     AwaitingChallengedProposalResponse(usize),
     // who's going to lose influence, and if the turn should end afterward
     AwaitingLoseInfluence(usize, bool),
+    // exchanger has drawn from the deck and must pick which cards to keep
+    AwaitingExchangeChoice,
+    // player picking their starting influence in a two player game
+    AwaitingInfluenceSelection(usize),
+    // examined player picking which card to show the inquisitor
+    AwaitingExamineShow(usize),
+    // examined player and the card they showed, inquisitor decides if it gets swapped out
+    AwaitingExamineDecision(usize, usize),
 
     ResolveProposal,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Character {
+    // the default is only ever used to fill unused slots
+    #[default]
     Duke,
     Assassin,
     Captain,
     Ambassador,
     Contessa,
+    Inquisitor,
 }
 
-static CHARACTER_VARIANTS: [Character; 5] = [
-    Duke,
-    Assassin,
-    Captain,
-    Ambassador,
-    Contessa,
-];
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Faction {
+    Loyalist,
+    Reformist,
+}
 
+impl Faction {
+    pub fn converted(self) -> Faction {
+        match self {
+            Faction::Loyalist => Faction::Reformist,
+            Faction::Reformist => Faction::Loyalist,
+        }
+    }
+}
 
-#[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
-struct Player {
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Player {
     money: u8,
-    influence_cards: Vec<(Character, bool)>, // (character, revealed)
+    influence_cards: InlineVec<(Character, bool), 2>, // (character, revealed)
+    faction: Option<Faction>, // only when playing with the reformation expansion
+}
+
+impl Player {
+    pub fn money(&self) -> u8 {
+        self.money
+    }
+
+    // every card including hidden ones - use Coup::cards_visible_to for what a player is allowed to see
+    pub fn influence_cards(&self) -> &[(Character, bool)] {
+        &self.influence_cards
+    }
+
+    pub fn faction(&self) -> Option<Faction> {
+        self.faction
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.influence_cards.iter().any(|card| !card.1)
+    }
+}
+
+// one influence card as seen by a particular player
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CardView {
+    Hidden,
+    Live(Character),
+    Revealed(Character),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CoupError {
+    InvalidConfig(String),
+    InvalidPlayerCount(u8),
+    // a position that can't come up in a real game, see CoupBuilder
+    InvalidSetup(String),
+    // the game has already been won or drawn
+    GameOver,
+    // (player who tried to act, player who has to act)
+    WrongPlayer(usize, usize),
+    // the action doesn't fit the current phase of the turn
+    WrongPhase(Action),
+    // (money needed, money the player has)
+    InsufficientFunds(u8, u8),
+    NoSuchPlayer(usize),
+    DeadTarget(usize),
+    // (player, card index)
+    BadCardIndex(usize, usize),
+    // anything else the rules don't allow right now
+    IllegalAction(Action),
+    // a card has to be drawn before anyone can act
+    ChancePending,
+    // there's no draw to resolve
+    NoChancePending,
+    NotInDeck(Character),
+    // text that doesn't follow the notation, see notation.rs
+    InvalidNotation(String),
+    // a game record that doesn't add up
+    InvalidRecord(String),
+    // a position the rules can't get to, see Coup::validate
+    InvalidState(String),
+    // a scenario file that can't be read, or doesn't play out the way it says, see scenario.rs
+    ScenarioFailed(String),
+}
+
+impl Display for CoupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CoupError::InvalidConfig(reason) => f.write_fmt(format_args!("invalid config: {reason}")),
+            CoupError::InvalidPlayerCount(num_players) => f.write_fmt(format_args!("can't play with {num_players} players")),
+            CoupError::InvalidSetup(reason) => f.write_fmt(format_args!("invalid setup: {reason}")),
+            CoupError::GameOver => f.write_str("the game is already over"),
+            CoupError::WrongPlayer(player_idx, expected_player_idx) => {
+                f.write_fmt(format_args!("player {player_idx} tried to act, but it's player {expected_player_idx}'s decision"))
+            }
+            CoupError::WrongPhase(action) => f.write_fmt(format_args!("\"{:?}\" can't be done at this point in the turn", action)),
+            CoupError::InsufficientFunds(needed, available) => f.write_fmt(format_args!("needs ${needed} but only has ${available}")),
+            CoupError::NoSuchPlayer(player_idx) => f.write_fmt(format_args!("there is no player {player_idx}")),
+            CoupError::DeadTarget(player_idx) => f.write_fmt(format_args!("player {player_idx} is already out of the game")),
+            CoupError::BadCardIndex(player_idx, card_idx) => {
+                f.write_fmt(format_args!("player {player_idx} has no live influence card at index {card_idx}"))
+            }
+            CoupError::IllegalAction(action) => f.write_fmt(format_args!("\"{:?}\" is not allowed right now", action)),
+            CoupError::ChancePending => f.write_str("a card has to be drawn before anyone can act"),
+            CoupError::NoChancePending => f.write_str("there's no card waiting to be drawn"),
+            CoupError::NotInDeck(character) => f.write_fmt(format_args!("there's no {:?} left in the deck", character)),
+            CoupError::InvalidNotation(reason) => f.write_fmt(format_args!("invalid notation: {reason}")),
+            CoupError::InvalidRecord(reason) => f.write_fmt(format_args!("invalid record: {reason}")),
+            CoupError::InvalidState(reason) => f.write_fmt(format_args!("invalid state: {reason}")),
+            CoupError::ScenarioFailed(reason) => f.write_fmt(format_args!("scenario failed: {reason}")),
+        }
+    }
 }
 
-#[derive(Debug)]
-pub enum CoupError {}
+impl std::error::Error for CoupError {}
 
 
-#[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "outcome::SavedCoup")]
 pub struct Coup {
+    config: GameConfig,
     turn: usize,
     current_player_idx: usize,
-    deck: Vec<Character>,
-    players: Vec<Player>,
+    deck: InlineVec<Character, MAX_CARDS>,
+    players: InlineVec<Player, { MAX_PLAYERS as usize }>,
 
     state: State,
     priority_player_idx: Option<usize>,
     proposal: Option<Action>,
     proposal_blocked_with: Option<Character>,
+    // cards drawn from the deck by an exchange that hasn't been resolved yet
+    exchange_cards: InlineVec<Character, { MAX_EXCHANGE_DRAW as usize }>,
+    // money paid for conversions, up for grabs by embezzling
+    treasury_reserve: u8,
+    // cards an inquisitor has been shown and let the owner keep
+    known_cards: KnownCards,
+    // draws waiting on a chance outcome, in the order they happen
+    pending_draws: InlineVec<Draw, MAX_PENDING_DRAWS>,
+    // (player, turn) for everyone who's been knocked out, in the order they went
+    eliminations: InlineVec<(usize, usize), { MAX_PLAYERS as usize }>,
+    history: History,
+    #[serde(skip)]
+    zobrist: Zobrist,
+}
+
+// games are equal when their positions are - the history and the zobrist keys are left out, so two
+// games compare equal however they got there and whether or not they've been keyed yet
+impl PartialEq for Coup {
+    fn eq(&self, other: &Self) -> bool {
+        self.config == other.config &&
+            self.turn == other.turn &&
+            self.current_player_idx == other.current_player_idx &&
+            self.deck == other.deck &&
+            self.players == other.players &&
+            self.state == other.state &&
+            self.priority_player_idx == other.priority_player_idx &&
+            self.proposal == other.proposal &&
+            self.proposal_blocked_with == other.proposal_blocked_with &&
+            self.exchange_cards == other.exchange_cards &&
+            self.treasury_reserve == other.treasury_reserve &&
+            self.known_cards == other.known_cards &&
+            self.pending_draws == other.pending_draws &&
+            self.eliminations == other.eliminations
+    }
+}
+
+impl Eq for Coup {}
+
+// equal games always have equal keys, so the key is all a hash needs
+impl Hash for Coup {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.zobrist_key());
+    }
 }
 
 impl Debug for Coup {
@@ -75,11 +266,19 @@ impl Debug for Coup {
         f.write_str(format!("T {} | P {} | P* {:?}\n", self.turn, self.current_player_idx, self.priority_player_idx).as_str())?;
 
         for (player_idx, player) in self.players.iter().enumerate() {
-            f.write_str(format!("\tP {player_idx}: ${} | {:?}, {:?}\n", player.money, player.influence_cards[0], player.influence_cards[1]).as_str())?;
+            f.write_str(format!("\tP {player_idx}: ${} | {:?}", player.money, player.influence_cards).as_str())?;
+
+            if let Some(faction) = player.faction {
+                f.write_str(format!(" | {:?}", faction).as_str())?;
+            }
+
+            f.write_str("\n")?;
         }
 
         if let Some(winner) = self.winner() {
             f.write_str(format!("Winner! {winner}").as_str())?;
+        } else if self.is_drawn() {
+            f.write_str("Draw!")?;
         }
 
         Ok(())
@@ -88,30 +287,157 @@ impl Debug for Coup {
 
 const PRINT_ACTIONS: bool = false;
 
+// a full hand plus everything an exchange can draw
+const MAX_EXCHANGE_POOL: usize = 2 + MAX_EXCHANGE_DRAW as usize;
+// every pair that can be kept out of the biggest pool
+const MAX_KEEP_CHOICES: usize = MAX_EXCHANGE_POOL * (MAX_EXCHANGE_POOL - 1) / 2;
+// the most draws that can be waiting at once - a second card for everyone at a two player table,
+// or a whole exchange
+const MAX_PENDING_DRAWS: usize = MAX_PLAYERS as usize;
+
 
 impl Coup {
-    pub fn new<R: Rng + Sized>(num_players: u8, rng: &mut R) -> Self {
-        let mut deck: Vec<Character> = CHARACTER_VARIANTS.iter()
-            .flat_map(|&card| std::iter::repeat(card).take(3))
+    pub fn new<R: Rng + Sized>(num_players: u8, rng: &mut R) -> Result<Self, CoupError> {
+        Self::with_config(GameConfig::official(num_players), rng)
+    }
+
+    pub fn with_config<R: Rng + Sized>(config: GameConfig, rng: &mut R) -> Result<Self, CoupError> {
+        config.validate()?;
+
+        let mut deck: Vec<Character> = config.characters().iter()
+            .flat_map(|&card| std::iter::repeat_n(card, config.copies_per_character as usize))
             .collect();
 
         deck.shuffle(rng);
 
-        let players = (0..num_players).map(|_| Player {
-            money: 2,
-            influence_cards: vec![(deck.remove(0), false), (deck.remove(0), false)],
-        }).collect();
+        let (players, state, priority_player_idx) = match config.setup {
+            SetupMode::Standard => {
+                let players = (0..config.num_players as usize).map(|player_idx| Player {
+                    money: config.starting_coins(player_idx),
+                    influence_cards: InlineVec::from_slice(&[(deck.remove(0), false), (deck.remove(0), false)]),
+                    faction: config.starting_faction(player_idx),
+                }).collect();
+
+                (players, State::AwaitingProposal, None)
+            }
+            SetupMode::TwoPlayer => {
+                // hands are empty until everyone has made their selection
+                let players = (0..config.num_players as usize).map(|player_idx| Player {
+                    money: config.starting_coins(player_idx),
+                    influence_cards: InlineVec::new(),
+                    faction: config.starting_faction(player_idx),
+                }).collect();
+
+                (players, State::AwaitingInfluenceSelection(0), Some(0))
+            }
+        };
 
-        Self {
+        let mut game = Self {
+            config,
             turn: 0,
-            state: State::AwaitingProposal,
+            state,
             current_player_idx: 0,
-            priority_player_idx: None,
+            priority_player_idx,
             proposal: None,
             proposal_blocked_with: None,
-            deck,
+            exchange_cards: InlineVec::new(),
+            treasury_reserve: 0,
+            known_cards: KnownCards::default(),
+            pending_draws: InlineVec::new(),
+            eliminations: InlineVec::new(),
+            history: History::default(),
+            zobrist: Zobrist::default(),
+            deck: InlineVec::from_slice(&deck),
             players,
-        }
+        };
+
+        game.rehash();
+        Ok(game)
+    }
+
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn player(&self, player_idx: usize) -> &Player {
+        &self.players[player_idx]
+    }
+
+    pub fn coins(&self, player_idx: usize) -> u8 {
+        self.players[player_idx].money
+    }
+
+    pub fn faction(&self, player_idx: usize) -> Option<Faction> {
+        self.players[player_idx].faction
+    }
+
+    pub fn revealed_cards(&self, player_idx: usize) -> impl Iterator<Item=Character> + '_ {
+        self.players[player_idx].influence_cards
+            .iter()
+            .filter(|card| card.1)
+            .map(|card| card.0)
+    }
+
+    pub fn num_live_cards(&self, player_idx: usize) -> usize {
+        self.player_active_influence_cards(player_idx).count()
+    }
+
+    // the owner's cards as the viewer is allowed to see them - revealed cards are public, live ones
+    // only to their owner or to an inquisitor who examined them
+    pub fn cards_visible_to(&self, viewer_idx: usize, owner_idx: usize) -> Vec<CardView> {
+        self.players[owner_idx].influence_cards
+            .iter()
+            .enumerate()
+            .map(|(card_idx, &(character, revealed))| {
+                if revealed {
+                    CardView::Revealed(character)
+                } else if viewer_idx == owner_idx || self.known_cards.contains(viewer_idx, owner_idx, card_idx) {
+                    CardView::Live(character)
+                } else {
+                    CardView::Hidden
+                }
+            })
+            .collect()
+    }
+
+    pub fn deck_size(&self) -> usize {
+        self.deck.len()
+    }
+
+    pub fn treasury_reserve(&self) -> u8 {
+        self.treasury_reserve
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn current_player_idx(&self) -> usize {
+        self.current_player_idx
+    }
+
+    pub fn priority_player_idx(&self) -> Option<usize> {
+        self.priority_player_idx
+    }
+
+    pub fn proposal(&self) -> Option<&Action> {
+        self.proposal.as_ref()
+    }
+
+    pub fn proposal_blocked_with(&self) -> Option<Character> {
+        self.proposal_blocked_with
     }
 
     // creates a clone of the game where things this player should not know have been randomized
@@ -119,15 +445,31 @@ impl Coup {
         let mut determinization = self.clone();
 
         // player doesn't know what's in anyone else's hand, so randomize the hidden cards
-        for opponent_idx in determinization.other_player_indexes(player_idx) {
+        // except for the ones they've been shown by examining
+        for &opponent_idx in &determinization.other_player_indexes(player_idx) {
             for card_idx in determinization.player_active_influence_cards(opponent_idx).collect::<Vec<usize>>() {
-                determinization.replace_influence_card(opponent_idx, card_idx, rng);
+                if !self.known_cards.contains(player_idx, opponent_idx, card_idx) {
+                    determinization.replace_influence_card(opponent_idx, card_idx, rng);
+                }
+            }
+        }
+
+        // player doesn't know what an opponent drew for their exchange
+        if !determinization.exchange_cards.is_empty() && determinization.current_player_idx != player_idx {
+            let num_drawn = determinization.exchange_cards.len();
+            let exchange_cards = std::mem::take(&mut determinization.exchange_cards);
+            determinization.deck.extend_from_slice(&exchange_cards);
+            determinization.deck.shuffle(rng);
+            for _ in 0..num_drawn {
+                let card = determinization.draw_card();
+                determinization.exchange_cards.push(card);
             }
         }
 
         // player doesn't know about the order of the deck
         determinization.deck.shuffle(rng);
 
+        determinization.rehash();
         determinization
     }
 
@@ -135,23 +477,38 @@ impl Coup {
         0..self.players.len()
     }
 
-    fn active_player(&self) -> &Player {
-        let idx = if let Some(priority_player_idx) = self.priority_player_idx {
+    // the player who has to make the next decision
+    pub fn active_player_idx(&self) -> usize {
+        if let Some(priority_player_idx) = self.priority_player_idx {
             priority_player_idx
         } else {
             self.current_player_idx
-        };
+        }
+    }
 
-        &self.players[idx]
+    fn active_player(&self) -> &Player {
+        &self.players[self.active_player_idx()]
     }
 
-    fn other_player_indexes(&self, exclude_idx: usize) -> Vec<usize> {
+    fn other_player_indexes(&self, exclude_idx: usize) -> InlineVec<usize, { MAX_PLAYERS as usize }> {
         (1..self.players.len())
             .map(|n| (exclude_idx + n) % self.players.len())
             .filter(|player_idx| !self.is_player_dead(*player_idx))
             .collect()
     }
 
+    // factions can't attack each other's members until only one faction is left
+    fn can_target(&self, player_idx: usize, target_player_idx: usize) -> bool {
+        let faction = self.players[player_idx].faction;
+        if faction.is_none() || faction != self.players[target_player_idx].faction {
+            return true;
+        }
+
+        self.players_indexes()
+            .filter(|idx| !self.is_player_dead(*idx))
+            .all(|idx| self.players[idx].faction == faction)
+    }
+
     fn go_next_turn(&mut self) {
         // reset state
         self.state = State::AwaitingProposal;
@@ -177,6 +534,10 @@ impl Coup {
     }
 
     fn replace_influence_card<R: Rng + Sized>(&mut self, player_idx: usize, card_idx: usize, rng: &mut R) {
+        // only used for determinizations, which never get rolled back
+        let mut undo = Undo::new(self);
+        self.forget_card(player_idx, card_idx, &mut undo);
+
         let card = self.players[player_idx].influence_cards.remove(card_idx);
         if card.1 {
             panic!("shouldn't be able to lose/replace a revealed/lost influence card");
@@ -195,13 +556,62 @@ impl Coup {
         }
     }
 
-    fn lose_influence_card(&mut self, player_idx: usize, card_idx: usize) {
+    // a card goes back into the deck in front of everyone, the replacement is drawn as a chance outcome
+    fn swap_influence_card(&mut self, player_idx: usize, card_idx: usize, undo: &mut Undo) {
+        self.forget_card(player_idx, card_idx, undo);
+
+        let card = self.players[player_idx].influence_cards[card_idx];
+        if card.1 {
+            panic!("shouldn't be able to lose/replace a revealed/lost influence card");
+        }
+
+        // the card stays in the hand until the draw replaces it
+        self.deck.push(card.0);
+        undo.push(Change::DeckPushed(1));
+        self.pending_draws.push(Draw::Influence(player_idx, card_idx));
+        undo.push(Change::DrawPushed);
+
+        self.record(Visibility::Public, EventKind::CardsShuffledBack(player_idx, 1));
+    }
+
+    // nobody can know what a card is once it's been swapped out
+    fn forget_card(&mut self, player_idx: usize, card_idx: usize, undo: &mut Undo) {
+        let known_cards = self.known_cards;
+        if self.known_cards.forget(player_idx, card_idx) {
+            undo.push(Change::KnownCards(known_cards));
+        }
+    }
+
+    // the deck is kept shuffled, so the top card is a random one
+    fn draw_card(&mut self) -> Character {
+        self.deck.remove(0)
+    }
+
+    // the live cards in the exchanger's hand followed by the cards they drew
+    fn exchange_pool(&self, player_idx: usize) -> InlineVec<Character, MAX_EXCHANGE_POOL> {
+        self.player_active_influence_cards(player_idx)
+            .map(|card_idx| self.players[player_idx].influence_cards[card_idx].0)
+            .chain(self.exchange_cards.iter().copied())
+            .collect()
+    }
+
+    fn lose_influence_card(&mut self, player_idx: usize, card_idx: usize, undo: &mut Undo) {
         // 'losing' an influence means your card is flipped up and revealed and doesn't count
+        undo.push(Change::Card(player_idx, card_idx, self.players[player_idx].influence_cards[card_idx]));
         self.players[player_idx].influence_cards[card_idx].1 = true;
+
+        let character = self.players[player_idx].influence_cards[card_idx].0;
+        self.record(Visibility::Public, EventKind::CardLost(player_idx, character));
+
+        if self.is_player_dead(player_idx) {
+            self.eliminations.push((player_idx, self.turn));
+            undo.push(Change::EliminationPushed);
+            self.record(Visibility::Public, EventKind::PlayerEliminated(player_idx));
+        }
     }
 
-    fn is_player_dead(&self, player_idx: usize) -> bool {
-        self.players[player_idx].influence_cards.iter().filter(|x| !x.1).count() == 0
+    pub fn is_player_dead(&self, player_idx: usize) -> bool {
+        !self.players[player_idx].is_alive()
     }
 
     fn player_active_influence_cards(&self, player_idx: usize) -> impl Iterator<Item=usize> + '_ {
@@ -226,36 +636,114 @@ impl Coup {
             .position(|e| !e.1 && e.0 == character)
     }
 
+    // everything the game is waiting on - leaving isn't listed, since anyone still in can do it
+    // whenever they like, but is_legal() and applying it accept it all the same
     pub fn actions(&self) -> Vec<Action> {
         let mut actions = Vec::with_capacity(self.players.len() * 2);
+        self.for_each_action(|action| actions.push(action));
+        actions
+    }
+
+    // fills the buffer with the legal actions, reusing its allocation
+    pub fn actions_into(&self, buffer: &mut Vec<Action>) {
+        buffer.clear();
+        self.for_each_action(|action| buffer.push(action));
+    }
+
+    pub fn num_actions(&self) -> usize {
+        let mut num_actions = 0;
+        self.for_each_action(|_| num_actions += 1);
+        num_actions
+    }
+
+    pub fn is_legal(&self, action: &Action) -> bool {
+        if let Action::Leave(_, _) = action {
+            return self.check_action(action).is_ok();
+        }
+
+        let action = action.normalized();
+        let mut legal = false;
+        self.for_each_action(|legal_action| legal |= legal_action == action);
+        legal
+    }
+
+    // a uniformly random legal action, without collecting them all first
+    pub fn random_action<R: Rng + Sized>(&self, rng: &mut R) -> Option<Action> {
+        let num_actions = self.num_actions();
+        if num_actions == 0 {
+            return None;
+        }
+
+        let mut remaining = rng.gen_range(0..num_actions);
+        let mut chosen = None;
+        self.for_each_action(|action| {
+            if remaining == 0 && chosen.is_none() {
+                chosen = Some(action);
+            }
+            remaining = remaining.saturating_sub(1);
+        });
+
+        chosen
+    }
+
+    // generates every legal action in order without allocating - everything else about legal actions is built on this
+    pub fn for_each_action<F: FnMut(Action)>(&self, mut f: F) {
+        // nobody gets to act until the draw is made
+        if self.is_chance_node() {
+            return;
+        }
 
         match self.state {
             State::AwaitingProposal => {
-                if self.players[self.current_player_idx].money >= 10 {
+                if self.players[self.current_player_idx].money >= self.config.forced_coup_threshold {
                     // forced coup at $10+
-                    for opponent_idx in self.other_player_indexes(self.current_player_idx) {
-                        actions.push(Action::Coup(self.current_player_idx, opponent_idx));
+                    for &opponent_idx in &self.other_player_indexes(self.current_player_idx) {
+                        if self.can_target(self.current_player_idx, opponent_idx) {
+                            f(Action::Coup(self.current_player_idx, opponent_idx));
+                        }
                     }
                 } else {
                     // income-ing is not a proposal - it just happens
-                    actions.push(Action::Income(self.current_player_idx));
-                    actions.push(Action::Propose(self.current_player_idx, Box::new(Action::ForeignAid(self.current_player_idx))));
-                    actions.push(Action::Propose(self.current_player_idx, Box::new(Action::Tax(self.current_player_idx))));
+                    f(Action::Income(self.current_player_idx));
+                    f(Action::ForeignAid(self.current_player_idx));
+                    f(Action::Tax(self.current_player_idx));
 
-                    for card_idx in self.player_active_influence_cards(self.current_player_idx) {
-                        actions.push(Action::Propose(self.current_player_idx, Box::new(Action::Exchange(self.current_player_idx, card_idx))));
+                    f(Action::Exchange(self.current_player_idx));
+
+                    if self.config.reformation {
+                        if self.players[self.current_player_idx].money >= self.config.convert_self_cost {
+                            f(Action::Convert(self.current_player_idx, self.current_player_idx));
+                        }
+
+                        if self.players[self.current_player_idx].money >= self.config.convert_other_cost {
+                            for &opponent_idx in &self.other_player_indexes(self.current_player_idx) {
+                                f(Action::Convert(self.current_player_idx, opponent_idx));
+                            }
+                        }
+
+                        if self.treasury_reserve > 0 {
+                            f(Action::Embezzle(self.current_player_idx));
+                        }
                     }
 
-                    for opponent_idx in self.other_player_indexes(self.current_player_idx) {
-                        if self.players[self.current_player_idx].money >= 7 {
+                    for &opponent_idx in &self.other_player_indexes(self.current_player_idx) {
+                        if self.config.inquisitor {
+                            f(Action::Examine(self.current_player_idx, opponent_idx));
+                        }
+
+                        if !self.can_target(self.current_player_idx, opponent_idx) {
+                            continue;
+                        }
+
+                        if self.players[self.current_player_idx].money >= self.config.coup_cost {
                             // coup-ing is not a proposal - it just happens
-                            actions.push(Action::Coup(self.current_player_idx, opponent_idx));
-                        } else if self.players[self.current_player_idx].money >= 3 {
-                            actions.push(Action::Propose(self.current_player_idx, Box::new(Action::Assassinate(self.current_player_idx, opponent_idx))));
+                            f(Action::Coup(self.current_player_idx, opponent_idx));
+                        } else if self.players[self.current_player_idx].money >= self.config.assassination_cost {
+                            f(Action::Assassinate(self.current_player_idx, opponent_idx));
                         }
 
                         if self.players[opponent_idx].money > 0 {
-                            actions.push(Action::Propose(self.current_player_idx, Box::new(Action::Steal(self.current_player_idx, opponent_idx))));
+                            f(Action::Steal(self.current_player_idx, opponent_idx));
                         }
                     }
                 }
@@ -264,12 +752,12 @@ impl Coup {
                 match self.priority_player_idx {
                     Some(priority_player_idx) => {
                         if self.current_player_idx != self.priority_player_idx.unwrap() {
-                            actions.push(Action::Pass(priority_player_idx));
+                            f(Action::Pass(priority_player_idx));
                             match &self.proposal {
                                 Some(proposal) => {
                                     // everyone can block foreign aid
                                     if let Action::ForeignAid(_) = proposal {
-                                        actions.push(Action::Block(priority_player_idx, Duke));
+                                        f(Action::Block(priority_player_idx, Duke));
                                     }
 
                                     // any character proposal action can be challenged
@@ -277,23 +765,29 @@ impl Coup {
                                     // this state of the game
                                     match proposal {
                                         Action::Tax(_) => {
-                                            actions.push(Action::Challenge(priority_player_idx));
+                                            f(Action::Challenge(priority_player_idx));
                                         }
                                         Action::Assassinate(_, target_player_idx) => {
-                                            actions.push(Action::Challenge(priority_player_idx));
+                                            f(Action::Challenge(priority_player_idx));
                                             if *target_player_idx == self.priority_player_idx.unwrap() {
-                                                actions.push(Action::Block(priority_player_idx, Contessa));
+                                                f(Action::Block(priority_player_idx, Contessa));
                                             }
                                         }
                                         Action::Steal(_, target_player_idx) => {
-                                            actions.push(Action::Challenge(priority_player_idx));
+                                            f(Action::Challenge(priority_player_idx));
                                             if *target_player_idx == self.priority_player_idx.unwrap() {
-                                                actions.push(Action::Block(priority_player_idx, Ambassador));
-                                                actions.push(Action::Block(priority_player_idx, Captain));
+                                                f(Action::Block(priority_player_idx, self.config.exchange_character()));
+                                                f(Action::Block(priority_player_idx, Captain));
                                             }
                                         }
-                                        Action::Exchange(_, _) => {
-                                            actions.push(Action::Challenge(priority_player_idx));
+                                        Action::Exchange(_) => {
+                                            f(Action::Challenge(priority_player_idx));
+                                        }
+                                        Action::Embezzle(_) => {
+                                            f(Action::Challenge(priority_player_idx));
+                                        }
+                                        Action::Examine(_, _) => {
+                                            f(Action::Challenge(priority_player_idx));
                                         }
                                         _ => {}
                                     }
@@ -314,12 +808,12 @@ impl Coup {
 
                         if priority_player_idx == self.current_player_idx {
                             // priority player is current player
-                            actions.push(Action::Challenge(self.current_player_idx));
-                            actions.push(Action::Relent(self.current_player_idx));
+                            f(Action::Challenge(self.current_player_idx));
+                            f(Action::Relent(self.current_player_idx));
                         } else {
                             // priority player is not the current player
-                            actions.push(Action::Challenge(priority_player_idx));
-                            actions.push(Action::Pass(priority_player_idx));
+                            f(Action::Challenge(priority_player_idx));
+                            f(Action::Pass(priority_player_idx));
                         }
                     }
                     None => unreachable!("priority_player_idx must be defined at this point")
@@ -331,7 +825,7 @@ impl Coup {
                     Some(priority_player_idx) => {
                         // can lose if forced, or on purpose
                         for card_idx in self.player_active_influence_cards(priority_player_idx) {
-                            actions.push(Action::Lose(priority_player_idx, card_idx));
+                            f(Action::Lose(priority_player_idx, card_idx));
                         }
 
                         match self.proposal_blocked_with {
@@ -339,7 +833,7 @@ impl Coup {
                             Some(proposal_blocked_with) => {
                                 // blocking player has the nuts, they can prove by revealing and win
                                 if let Some(card_idx) = self.find_player_active_character(priority_player_idx, proposal_blocked_with) {
-                                    actions.push(Action::Reveal(priority_player_idx, card_idx));
+                                    f(Action::Reveal(priority_player_idx, card_idx));
                                 }
                             }
                         }
@@ -349,204 +843,536 @@ impl Coup {
             State::AwaitingChallengedProposalResponse(_) => {
                 // can lose if forced, or on purpose
                 for card_idx in self.player_active_influence_cards(self.current_player_idx) {
-                    actions.push(Action::Lose(self.current_player_idx, card_idx));
+                    f(Action::Lose(self.current_player_idx, card_idx));
                 }
 
                 match &self.proposal {
                     None => unreachable!("proposal must be defined at this point"),
+                    Some(Action::Embezzle(_)) => {
+                        // embezzling is a claim to not have a duke, so it's proven by showing there isn't one
+                        if self.find_player_active_character(self.current_player_idx, Duke).is_none() {
+                            f(Action::Disprove(self.current_player_idx));
+                        }
+                    }
                     Some(proposal) => {
                         let required_character = match proposal {
                             Action::Tax(_) => Duke,
                             Action::Assassinate(_, _) => Assassin,
                             Action::Steal(_, _) => Captain,
-                            Action::Exchange(_, _) => Ambassador,
+                            Action::Exchange(_) => self.config.exchange_character(),
+                            Action::Examine(_, _) => Inquisitor,
                             _ => panic!("{:?} is not a blockable action", proposal),
                         };
 
                         if let Some(card_idx) = self.find_player_active_character(self.current_player_idx, required_character) {
-                            actions.push(Action::Reveal(self.current_player_idx, card_idx));
+                            f(Action::Reveal(self.current_player_idx, card_idx));
                         }
                     }
                 }
             }
             State::AwaitingLoseInfluence(loser_player_idx, _) => {
                 for card_idx in self.player_active_influence_cards(loser_player_idx) {
-                    actions.push(Action::Lose(loser_player_idx, card_idx));
+                    f(Action::Lose(loser_player_idx, card_idx));
                 }
             }
+            State::AwaitingExchangeChoice => {
+                // keep as many cards as there are live cards in hand, any combination from the pool
+                let pool = self.exchange_pool(self.current_player_idx);
+                let num_live = self.player_active_influence_cards(self.current_player_idx).count();
+
+                // the same characters can turn up more than once in the pool, but it's the same choice
+                let mut kept: InlineVec<(Character, Option<Character>), MAX_KEEP_CHOICES> = InlineVec::new();
+                let mut keep = |first: Character, second: Option<Character>| {
+                    if !kept.contains(&(first, second)) {
+                        kept.push((first, second));
+                        f(Action::Keep(self.current_player_idx, first, second));
+                    }
+                };
+
+                for first_idx in 0..pool.len() {
+                    if num_live == 1 {
+                        keep(pool[first_idx], None);
+                        continue;
+                    }
+
+                    for second_idx in (first_idx + 1)..pool.len() {
+                        // same pair of characters in either order is the same choice
+                        let (first, second) = if (pool[first_idx] as usize) <= (pool[second_idx] as usize) {
+                            (pool[first_idx], pool[second_idx])
+                        } else {
+                            (pool[second_idx], pool[first_idx])
+                        };
+
+                        keep(first, Some(second));
+                    }
+                }
+            }
+            State::AwaitingInfluenceSelection(player_idx) => {
+                // every player has a full set of characters to pick from
+                for character in self.config.characters() {
+                    f(Action::ChooseInfluence(player_idx, character));
+                }
+            }
+            State::AwaitingExamineShow(examined_player_idx) => {
+                // the examined player gets to pick which card is shown
+                for card_idx in self.player_active_influence_cards(examined_player_idx) {
+                    f(Action::Show(examined_player_idx, card_idx));
+                }
+            }
+            State::AwaitingExamineDecision(_, _) => {
+                f(Action::ForceSwap(self.current_player_idx));
+                f(Action::Release(self.current_player_idx));
+            }
             State::ResolveProposal => {
-                actions.push(Action::Resolve(self.current_player_idx));
+                f(Action::Resolve(self.current_player_idx));
             }
         }
+    }
 
-        actions
+    // money the current player has to pay up front for an action
+    fn action_cost(&self, action: &Action) -> u8 {
+        match action {
+            Action::Coup(_, _) => self.config.coup_cost,
+            Action::Assassinate(_, _) => self.config.assassination_cost,
+            Action::Convert(player_idx, target_player_idx) if player_idx == target_player_idx => self.config.convert_self_cost,
+            Action::Convert(_, _) => self.config.convert_other_cost,
+            _ => 0,
+        }
     }
 
+    // checks the action against the current state so a bad action is an error instead of a broken game
+    fn check_action(&self, action: &Action) -> Result<(), CoupError> {
+        if self.is_chance_node() {
+            return Err(CoupError::ChancePending);
+        }
+
+        if self.is_over() {
+            return Err(CoupError::GameOver);
+        }
+
+        let player_idx = action.player_idx();
+        if player_idx >= self.players.len() {
+            return Err(CoupError::NoSuchPlayer(player_idx));
+        }
+
+        // anyone still in can leave at any point, whoever's decision it is
+        if let Action::Leave(_, _) = action {
+            // nobody has a hand to reveal until everyone has picked
+            if let State::AwaitingInfluenceSelection(_) = self.state {
+                return Err(CoupError::WrongPhase(*action));
+            }
+
+            if self.is_player_dead(player_idx) {
+                return Err(CoupError::DeadTarget(player_idx));
+            }
+
+            return Ok(());
+        }
+
+        if player_idx != self.active_player_idx() {
+            return Err(CoupError::WrongPlayer(player_idx, self.active_player_idx()));
+        }
+
+        let right_phase = match self.state {
+            State::AwaitingProposal => action.is_proposal() || matches!(action, Action::Income(_) | Action::Coup(_, _) | Action::Convert(_, _)),
+            State::AwaitingProposalResponse(_) => matches!(action, Action::Pass(_) | Action::Block(_, _) | Action::Challenge(_)),
+            State::AwaitingProposalBlockResponse(_) => matches!(action, Action::Pass(_) | Action::Relent(_) | Action::Challenge(_)),
+            State::AwaitingChallengedBlockResponse(_, _) => matches!(action, Action::Lose(_, _) | Action::Reveal(_, _)),
+            State::AwaitingChallengedProposalResponse(_) => matches!(action, Action::Lose(_, _) | Action::Reveal(_, _) | Action::Disprove(_)),
+            State::AwaitingLoseInfluence(_, _) => matches!(action, Action::Lose(_, _)),
+            State::AwaitingExchangeChoice => matches!(action, Action::Keep(_, _, _)),
+            State::AwaitingInfluenceSelection(_) => matches!(action, Action::ChooseInfluence(_, _)),
+            State::AwaitingExamineShow(_) => matches!(action, Action::Show(_, _)),
+            State::AwaitingExamineDecision(_, _) => matches!(action, Action::ForceSwap(_) | Action::Release(_)),
+            State::ResolveProposal => matches!(action, Action::Resolve(_)),
+        };
+
+        if !right_phase {
+            return Err(CoupError::WrongPhase(*action));
+        }
+
+        let cost = self.action_cost(action);
+        if self.players[player_idx].money < cost {
+            return Err(CoupError::InsufficientFunds(cost, self.players[player_idx].money));
+        }
+
+        if let Some(target_player_idx) = action.target_player_idx() {
+            if target_player_idx >= self.players.len() {
+                return Err(CoupError::NoSuchPlayer(target_player_idx));
+            }
+
+            if self.is_player_dead(target_player_idx) {
+                return Err(CoupError::DeadTarget(target_player_idx));
+            }
+        }
+
+        if let Action::Lose(_, card_idx) | Action::Reveal(_, card_idx) | Action::Show(_, card_idx) = *action {
+            if !self.player_active_influence_cards(player_idx).any(|idx| idx == card_idx) {
+                return Err(CoupError::BadCardIndex(player_idx, card_idx));
+            }
+        }
+
+        // everything else comes down to the rules in actions()
+        if !self.is_legal(action) {
+            return Err(CoupError::IllegalAction(*action));
+        }
+
+        Ok(())
+    }
+
+    // applies the action and makes any draws it leads to at random
     pub fn apply_action<R: Rng + Sized>(&self, action: Action, rng: &mut R) -> Result<Coup, CoupError> {
         let mut game = self.clone();
 
+        // nothing is going to be taken back, so there's no need to keep track
+        let mut undo = Undo::untracked(&game);
+        game.transition(action, &mut undo)?;
+        game.draw_randomly(rng, &mut undo);
+        game.update_keys(&undo);
+
+        Ok(game)
+    }
+
+    // applies the action without drawing anything - if it leads to a draw the game is left at a chance node,
+    // see chance_outcomes() and apply_chance()
+    pub fn apply_deterministic(&self, action: Action) -> Result<Coup, CoupError> {
+        let mut game = self.clone();
+
+        let mut undo = Undo::untracked(&game);
+        game.transition(action, &mut undo)?;
+        game.update_keys(&undo);
+
+        Ok(game)
+    }
+
+    // apply_action() without the clone, the returned undo takes it back
+    pub fn apply_action_mut<R: Rng + Sized>(&mut self, action: Action, rng: &mut R) -> Result<Undo, CoupError> {
+        let mut undo = Undo::new(self);
+        self.transition(action, &mut undo)?;
+        self.draw_randomly(rng, &mut undo);
+        self.update_keys(&undo);
+
+        Ok(undo)
+    }
+
+    // apply_deterministic() without the clone, the returned undo takes it back
+    pub fn apply_deterministic_mut(&mut self, action: Action) -> Result<Undo, CoupError> {
+        let mut undo = Undo::new(self);
+        self.transition(action, &mut undo)?;
+        self.update_keys(&undo);
+
+        Ok(undo)
+    }
+
+    fn draw_randomly<R: Rng + Sized>(&mut self, rng: &mut R, undo: &mut Undo) {
+        while self.is_chance_node() {
+            let character = self.deck[rng.gen_range(0..self.deck.len())];
+            self.draw(character, undo);
+        }
+    }
+
+    // checks and applies the action, with every change it makes going into the undo
+    fn transition(&mut self, action: Action, undo: &mut Undo) -> Result<(), CoupError> {
+        self.check_action(&action)?;
+        let action = action.normalized();
+
         if PRINT_ACTIONS {
             println!("T{}: {} | {:?} -> ${} {:?} | {:?}", self.turn, self.current_player_idx, self.priority_player_idx, self.active_player().money, self.active_player().influence_cards, action);
         }
 
+        self.record_action(&action);
+
         match action {
-            Action::Propose(_, proposed_action) => {
+            proposal if proposal.is_proposal() => {
 
                 // pay for assassinate proposal
-                if let Action::Assassinate(_, _) = *proposed_action {
-                    game.players[game.current_player_idx].money -= 3;
+                if let Action::Assassinate(_, _) = proposal {
+                    self.move_coins(Purse::Player(self.current_player_idx), Purse::Bank, self.config.assassination_cost, undo);
                 }
 
-                game.proposal = Some(proposed_action.deref().clone());
-                game.state = State::AwaitingProposalResponse(game.other_player_indexes(self.current_player_idx).len());
-                game.priority_player_idx = Some(game.next_prio_player_idx());
+                self.proposal = Some(proposal);
+                self.state = State::AwaitingProposalResponse(self.other_player_indexes(self.current_player_idx).len());
+                self.priority_player_idx = Some(self.next_prio_player_idx());
             }
             Action::Income(player_idx) => {
-                game.players[player_idx].money += 1;
-                game.go_next_turn();
+                self.move_coins(Purse::Bank, Purse::Player(player_idx), 1, undo);
+                self.go_next_turn();
             }
             Action::Coup(_, target_player_idx) => {
-                game.players[game.current_player_idx].money -= 7;
-                game.state = State::AwaitingLoseInfluence(target_player_idx, true);
+                self.move_coins(Purse::Player(self.current_player_idx), Purse::Bank, self.config.coup_cost, undo);
+                self.state = State::AwaitingLoseInfluence(target_player_idx, true);
+                self.priority_player_idx = Some(target_player_idx);
             }
             Action::Block(_, character) => {
-                let blocking_player_idx = game.priority_player_idx.expect("priority should exist and the acting player should have priority");
-                game.state = State::AwaitingProposalBlockResponse(blocking_player_idx);
-                game.proposal_blocked_with = Some(character);
-                game.priority_player_idx = None;
-                game.go_next_prio();
+                let blocking_player_idx = self.priority_player_idx.expect("priority should exist and the acting player should have priority");
+                self.state = State::AwaitingProposalBlockResponse(blocking_player_idx);
+                self.proposal_blocked_with = Some(character);
+                self.priority_player_idx = None;
+                self.go_next_prio();
             }
             Action::Relent(_) => {
-                game.go_next_turn();
+                self.go_next_turn();
+            }
+            Action::Convert(player_idx, target_player_idx) => {
+                // conversion is not a proposal - it just happens, and the payment goes into the reserve
+                let cost = if player_idx == target_player_idx {
+                    self.config.convert_self_cost
+                } else {
+                    self.config.convert_other_cost
+                };
+
+                // the reserve is capped like a purse, and whatever doesn't fit is paid to the bank
+                let into_reserve = self.move_coins(Purse::Player(player_idx), Purse::TreasuryReserve, cost, undo);
+                self.move_coins(Purse::Player(player_idx), Purse::Bank, cost - into_reserve, undo);
+
+                let target = &mut self.players[target_player_idx];
+                undo.push(Change::Faction(target_player_idx, target.faction));
+                target.faction = target.faction.map(Faction::converted);
+
+                if let Some(faction) = target.faction {
+                    self.record(Visibility::Public, EventKind::FactionChanged(target_player_idx, faction));
+                }
+
+                self.go_next_turn();
+            }
+            Action::Disprove(player_idx) => {
+                // the whole hand was shown to the table, so all of it gets swapped out
+                for card_idx in self.player_active_influence_cards(player_idx).collect::<Vec<usize>>() {
+                    let character = self.players[player_idx].influence_cards[card_idx].0;
+                    self.record(Visibility::Public, EventKind::CardRevealed(player_idx, character));
+                    self.swap_influence_card(player_idx, card_idx, undo);
+                }
+
+                match self.state {
+                    State::AwaitingChallengedProposalResponse(challenger_player_idx) => {
+                        self.state = State::AwaitingLoseInfluence(challenger_player_idx, false);
+                        self.priority_player_idx = Some(challenger_player_idx);
+                    }
+                    _ => unreachable!("can only disprove if current state is awaiting challenge response")
+                }
             }
             Action::Challenge(challenger_player_idx) => {
-                match game.state {
+                match self.state {
                     State::AwaitingProposalResponse(_) => {
-                        game.state = State::AwaitingChallengedProposalResponse(challenger_player_idx);
-                        game.priority_player_idx = Some(game.current_player_idx);
+                        self.state = State::AwaitingChallengedProposalResponse(challenger_player_idx);
+                        self.priority_player_idx = Some(self.current_player_idx);
                     }
                     State::AwaitingProposalBlockResponse(blocker_player_idx) => {
-                        game.state = State::AwaitingChallengedBlockResponse(blocker_player_idx, challenger_player_idx);
-                        game.priority_player_idx = Some(blocker_player_idx);
+                        self.state = State::AwaitingChallengedBlockResponse(blocker_player_idx, challenger_player_idx);
+                        self.priority_player_idx = Some(blocker_player_idx);
                     }
                     _ => unreachable!("only the proposal and block actions can be challenged")
                 }
             }
             Action::Lose(loser_player_idx, card_idx) => {
-                match game.state {
+                match self.state {
                     State::AwaitingChallengedProposalResponse(_) => {
-                        game.lose_influence_card(loser_player_idx, card_idx);
-                        game.go_next_turn();
+                        self.lose_influence_card(loser_player_idx, card_idx, undo);
+                        self.go_next_turn();
                     }
                     State::AwaitingChallengedBlockResponse(_, _) => {
-                        game.lose_influence_card(loser_player_idx, card_idx);
-                        game.priority_player_idx = Some(game.current_player_idx);
-                        game.state = State::ResolveProposal;
-
-                        if game.is_player_dead(game.current_player_idx) {
-                            game.go_next_turn();
+                        // the block fell through, so the proposal goes ahead as if it was never blocked
+                        self.lose_influence_card(loser_player_idx, card_idx, undo);
+                        self.proposal_blocked_with = None;
+                        self.priority_player_idx = Some(self.current_player_idx);
+                        self.state = State::ResolveProposal;
+
+                        if self.is_player_dead(self.current_player_idx) {
+                            self.go_next_turn();
                         }
                     }
                     State::AwaitingLoseInfluence(_, end_turn) => {
-                        game.lose_influence_card(loser_player_idx, card_idx);
-                        game.priority_player_idx = Some(game.current_player_idx);
-                        game.state = State::ResolveProposal;
+                        self.lose_influence_card(loser_player_idx, card_idx, undo);
+                        self.priority_player_idx = Some(self.current_player_idx);
+                        self.state = State::ResolveProposal;
 
                         // loss was not a challenge loss, so it was assassinate or coup, and so turn should end
                         if end_turn {
-                            game.go_next_turn();
+                            self.go_next_turn();
                         }
 
-                        if game.is_player_dead(game.current_player_idx) {
-                            game.go_next_turn();
+                        if self.is_player_dead(self.current_player_idx) {
+                            self.go_next_turn();
                         }
                     }
                     _ => unreachable!("can only lose if current state is awaiting lose influence")
                 }
             }
             Action::Reveal(player_idx, card_idx) => {
-                game.replace_influence_card(player_idx, card_idx, rng);
-                match game.state {
+                let character = self.players[player_idx].influence_cards[card_idx].0;
+                self.record(Visibility::Public, EventKind::CardRevealed(player_idx, character));
+                self.swap_influence_card(player_idx, card_idx, undo);
+                match self.state {
                     State::AwaitingChallengedBlockResponse(_, challenger_player_idx) => {
-                        game.state = State::AwaitingLoseInfluence(challenger_player_idx, true);
-                        game.priority_player_idx = Some(challenger_player_idx);
+                        self.state = State::AwaitingLoseInfluence(challenger_player_idx, true);
+                        self.priority_player_idx = Some(challenger_player_idx);
                     }
                     State::AwaitingChallengedProposalResponse(challenger_player_idx) => {
-                        game.state = State::AwaitingLoseInfluence(challenger_player_idx, false);
-                        game.priority_player_idx = Some(challenger_player_idx);
+                        self.state = State::AwaitingLoseInfluence(challenger_player_idx, false);
+                        self.priority_player_idx = Some(challenger_player_idx);
                     }
                     _ => unreachable!("can only reveal if current state is awaiting block or challenge response")
                 }
             }
             Action::Pass(_) => {
-                match game.state {
+                match self.state {
                     State::AwaitingProposalBlockResponse(_) => {
-                        game.go_next_prio();
+                        self.go_next_prio();
                     },
                     State::AwaitingProposalResponse(ref mut num_remaining_passers) => {
                         *num_remaining_passers -= 1;
 
                         if *num_remaining_passers == 0 {
-                            game.state = State::ResolveProposal;
-                            game.priority_player_idx = Some(game.current_player_idx);
+                            self.state = State::ResolveProposal;
+                            self.priority_player_idx = Some(self.current_player_idx);
                         } else {
-                            game.go_next_prio();
+                            self.go_next_prio();
                         }
                     }
                     _ => unreachable!("should be in the awaiting proposal/proposal block response phase")
                 }
             }
             Action::Resolve(_) => {
-                match &game.proposal {
+                match &self.proposal {
                     None => {}
                     Some(proposal) => {
                         match proposal {
                             Action::ForeignAid(_) => {
-                                game.players[game.current_player_idx].money += 2;
-                                game.go_next_turn();
+                                self.move_coins(Purse::Bank, Purse::Player(self.current_player_idx), self.config.foreign_aid, undo);
+                                self.go_next_turn();
                             }
                             Action::Tax(_) => {
-                                game.players[game.current_player_idx].money += 3;
-                                game.go_next_turn();
+                                self.move_coins(Purse::Bank, Purse::Player(self.current_player_idx), self.config.tax, undo);
+                                self.go_next_turn();
                             }
                             Action::Assassinate(_, target_player_idx) => {
                                 // target player could already be dead from losing a challenge
-                                if game.is_player_dead(*target_player_idx) {
-                                    game.go_next_turn();
+                                if self.is_player_dead(*target_player_idx) {
+                                    self.go_next_turn();
                                 } else {
-                                    game.state = State::AwaitingLoseInfluence(*target_player_idx, true);
+                                    self.state = State::AwaitingLoseInfluence(*target_player_idx, true);
+                                    self.priority_player_idx = Some(*target_player_idx);
                                 }
                             }
                             Action::Steal(_, target_player_idx) => {
-                                let n = {
-                                    if game.players[*target_player_idx].money >= 2 {
-                                        2
-                                    } else if game.players[*target_player_idx].money == 1 {
-                                        1
-                                    } else {
-                                        panic!("you shouldn't be able to steal from someone with $0")
-                                    }
-                                };
-                                game.players[game.current_player_idx].money += n;
-                                game.players[*target_player_idx].money -= n;
-                                game.go_next_turn();
+                                // target player could already be dead, and whatever they kept is out of play
+                                if !self.is_player_dead(*target_player_idx) {
+                                    let n = self.players[*target_player_idx].money.min(self.config.steal_amount);
+                                    self.move_coins(Purse::Player(*target_player_idx), Purse::Player(self.current_player_idx), n, undo);
+                                }
+                                self.go_next_turn();
+                            }
+                            Action::Examine(_, examined_player_idx) => {
+                                // target player could already be dead from losing a challenge
+                                if self.is_player_dead(*examined_player_idx) {
+                                    self.go_next_turn();
+                                } else {
+                                    self.state = State::AwaitingExamineShow(*examined_player_idx);
+                                    self.priority_player_idx = Some(*examined_player_idx);
+                                }
+                            }
+                            Action::Embezzle(_) => {
+                                self.move_coins(Purse::TreasuryReserve, Purse::Player(self.current_player_idx), self.treasury_reserve, undo);
+                                self.go_next_turn();
                             }
-                            Action::Exchange(_, card_idx) => {
-                                game.replace_influence_card(game.current_player_idx, *card_idx, rng);
-                                game.go_next_turn();
+                            Action::Exchange(_) => {
+                                for _ in 0..self.config.exchange_draw {
+                                    self.pending_draws.push(Draw::Exchange(self.current_player_idx));
+                                    undo.push(Change::DrawPushed);
+                                }
+                                self.state = State::AwaitingExchangeChoice;
                             }
                             _ => unreachable!("proposal is not actionable")
                         }
                     }
                 }
             }
+            Action::Keep(player_idx, first, second) => {
+                // everything in the pool that isn't kept goes back into the deck
+                let mut pool = self.exchange_pool(player_idx);
+                undo.push(Change::ExchangeCardsCleared(std::mem::take(&mut self.exchange_cards)));
+
+                let live_card_idxs: Vec<usize> = self.player_active_influence_cards(player_idx).collect();
+                for (card_idx, kept) in live_card_idxs.into_iter().zip([Some(first), second]) {
+                    let kept = kept.expect("must keep a card for every live influence");
+                    let pool_idx = pool.iter().position(|&c| c == kept).expect("kept card must come from the pool");
+                    undo.push(Change::Card(player_idx, card_idx, self.players[player_idx].influence_cards[card_idx]));
+                    self.players[player_idx].influence_cards[card_idx].0 = pool.remove(pool_idx);
+                    self.forget_card(player_idx, card_idx, undo);
+                }
+
+                self.record(Visibility::Public, EventKind::CardsShuffledBack(player_idx, pool.len()));
+                undo.push(Change::DeckPushed(pool.len()));
+                self.deck.extend_from_slice(&pool);
+                self.go_next_turn();
+            }
+            Action::Show(player_idx, card_idx) => {
+                // only the inquisitor gets to see the card
+                let known_cards = self.known_cards;
+                if self.known_cards.insert(self.current_player_idx, player_idx, card_idx) {
+                    undo.push(Change::KnownCards(known_cards));
+                }
+
+                let character = self.players[player_idx].influence_cards[card_idx].0;
+                self.record(Visibility::Private(self.current_player_idx), EventKind::CardShown(player_idx, card_idx, character));
+                self.state = State::AwaitingExamineDecision(player_idx, card_idx);
+                self.priority_player_idx = Some(self.current_player_idx);
+            }
+            Action::ForceSwap(_) => {
+                match self.state {
+                    State::AwaitingExamineDecision(examined_player_idx, card_idx) => {
+                        self.swap_influence_card(examined_player_idx, card_idx, undo);
+                        self.go_next_turn();
+                    }
+                    _ => unreachable!("can only force a swap after being shown a card")
+                }
+            }
+            Action::Release(_) => {
+                self.go_next_turn();
+            }
+            Action::ChooseInfluence(player_idx, character) => {
+                // the rest of the player's set is shuffled into the deck, so just take the pick from it
+                let deck_idx = self.deck.iter().position(|&c| c == character).expect("selected character must be in the deck");
+                self.deck.remove(deck_idx);
+                undo.push(Change::DeckRemoved(deck_idx, character));
+                self.players[player_idx].influence_cards.push((character, false));
+                undo.push(Change::CardPushed(player_idx));
+
+                if player_idx + 1 < self.players.len() {
+                    self.state = State::AwaitingInfluenceSelection(player_idx + 1);
+                    self.priority_player_idx = Some(player_idx + 1);
+                } else {
+                    // everyone has picked, deal the second influence at random
+                    for player_idx in self.players_indexes() {
+                        self.pending_draws.push(Draw::Influence(player_idx, 1));
+                        undo.push(Change::DrawPushed);
+                    }
+
+                    self.state = State::AwaitingProposal;
+                    self.priority_player_idx = None;
+                }
+            }
+            Action::Leave(player_idx, _) => {
+                self.leave(player_idx, undo);
+            }
             _ => unreachable!("invalid action")
         }
 
-        Ok(game)
+        debug_assert_eq!(self.validate(), Ok(()), "after {:?}", action);
+        Ok(())
+    }
+
+    // with the reformation expansion, the faction the last player standing belongs to
+    pub fn winning_faction(&self) -> Option<Faction> {
+        self.winner().and_then(|winner| self.players[winner].faction)
     }
 
     pub fn winner(&self) -> Option<usize> {
+        // hands are still being picked, so nobody is out yet
+        if let State::AwaitingInfluenceSelection(_) = self.state {
+            return None;
+        }
+
         let game_over = self.players
             .iter()
             .filter(|player| {
@@ -602,9 +1428,10 @@ mod tests {
     use criterion::black_box;
     use rand::{Rng, thread_rng};
     use crate::action::{Action};
-    use crate::action::Action::{Income, Lose, Pass, Assassinate, Resolve, Challenge, Reveal, Steal, Block};
-    use crate::Character::{Ambassador, Assassin, Captain, Duke};
-    use crate::{Coup};
+    use crate::action::Action::{Income, ForeignAid, Tax, Lose, Pass, Relent, Assassinate, Resolve, Challenge, Reveal, Steal, Block, Exchange, Keep, ChooseInfluence, Convert, Embezzle, Disprove, Examine, Show, ForceSwap, Release};
+    use crate::Character::{Ambassador, Assassin, Captain, Contessa, Duke, Inquisitor};
+    use crate::Faction::{Loyalist, Reformist};
+    use crate::{CardView, Coup, CoupBuilder, CoupError, Departure, EventKind, GameConfig, State};
 
     fn find_action(game: &Coup, f: Box<dyn Fn(&Action) -> bool>) -> Action {
         let actions = game.actions();
@@ -614,7 +1441,7 @@ mod tests {
                 panic!("action was not found")
             }
             Some(action) => {
-                action.clone()
+                *action
             }
         }
     }
@@ -631,10 +1458,32 @@ mod tests {
         }
     }
 
+    // plays random actions until someone wins, panicking if the game gets stuck
+    fn play_random_game(mut coup: Coup) -> Coup {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let mut actions = coup.actions();
+            if actions.is_empty() {
+                panic!("no actions generated during unfinished game")
+            }
+
+            let random_index = rng.gen_range(0..actions.len());
+            let random_action = actions.remove(random_index);
+
+            coup = coup.apply_action(random_action, &mut rng).unwrap();
+
+            if coup.winner().is_some() {
+                break;
+            }
+        }
+
+        coup
+    }
+
     #[test]
     fn check_deterministic() {
         let mut rng = thread_rng();
-        let mut coup = Coup::new(4, &mut rng);
+        let mut coup = Coup::new(4, &mut rng).unwrap();
         for _ in 0..10 {
             let mut actions = coup.actions();
             if actions.is_empty() {
@@ -653,17 +1502,323 @@ mod tests {
     }
 
     #[test]
-    fn complete_game() {
+    fn large_tables() {
+        let mut rng = thread_rng();
+
+        for num_players in 7..=10 {
+            let coup = Coup::new(num_players, &mut rng).unwrap();
+            assert_eq!(coup.deck.len(), coup.config.num_cards() - num_players as usize * 2);
+
+            play_random_game(coup);
+        }
+    }
+
+    #[test]
+    fn two_player_setup() {
+        let mut rng = thread_rng();
+        let mut coup = Coup::with_config(GameConfig::two_player(), &mut rng).unwrap();
+
+        // starting player gets one less coin
+        assert_eq!(coup.players[0].money, 1);
+        assert_eq!(coup.players[1].money, 2);
+
+        // p0 picks first from a full set of characters
+        assert_eq!(coup.actions().len(), 5);
+        assert!(coup.actions().iter().all(|a| matches!(a, ChooseInfluence(0, _))));
+        assert_eq!(coup.winner(), None);
+        coup = try_action(coup, Box::new(|a| *a == ChooseInfluence(0, Duke)));
+
+        // p1 has no cards yet, but that doesn't put them out of the game
+        assert_eq!(coup.winner(), None);
+        assert!(coup.actions().iter().all(|a| matches!(a, ChooseInfluence(1, _))));
+
+        // still nobody has won while the second cards are waiting to be dealt
+        let dealing = coup.apply_deterministic(ChooseInfluence(1, Duke)).unwrap();
+        assert!(dealing.is_chance_node());
+        assert_eq!(dealing.winner(), None);
+
+        coup = try_action(coup, Box::new(|a| *a == ChooseInfluence(1, Duke)));
+        assert_eq!(coup.winner(), None);
+
+        // both players are dealt a second card at random
+        assert_eq!(coup.players[0].influence_cards[0], (Duke, false));
+        assert_eq!(coup.players[1].influence_cards[0], (Duke, false));
+        assert_eq!(coup.players[0].influence_cards.len(), 2);
+        assert_eq!(coup.players[1].influence_cards.len(), 2);
+        assert_eq!(coup.deck.len(), 11);
+
+        find_action(&coup, Box::new(|a| *a == Income(0)));
+
+        let coup = play_random_game(coup);
+        assert!(coup.winner().is_some());
+        assert!(coup.turn > 0);
+    }
+
+    #[test]
+    fn reformation_targeting() {
+        let mut rng = thread_rng();
+        let mut coup = Coup::with_config(GameConfig::reformation(4), &mut rng).unwrap();
+
+        assert_eq!(coup.players[0].faction, Some(Loyalist));
+        assert_eq!(coup.players[1].faction, Some(Reformist));
+        assert_eq!(coup.players[2].faction, Some(Loyalist));
+
+        // p0 can only attack the other faction
+        coup.players[0].money = 7;
+        assert!(coup.actions().contains(&Action::Coup(0, 1)));
+        assert!(!coup.actions().contains(&Action::Coup(0, 2)));
+        assert!(!coup.actions().contains(&Steal(0, 2)));
+
+        // p0 converts p2, so now they can be attacked
+        coup = try_action(coup, Box::new(|a| *a == Convert(0, 2)));
+        assert_eq!(coup.players[0].money, 5);
+        assert_eq!(coup.players[2].faction, Some(Reformist));
+        assert_eq!(coup.treasury_reserve, 2);
+
+        // once everyone left is in one faction, anyone can be targeted
+        coup.players[1].influence_cards[0].1 = true;
+        coup.players[1].influence_cards[1].1 = true;
+        coup.players[3].influence_cards[0].1 = true;
+        coup.players[3].influence_cards[1].1 = true;
+        coup.players[0].faction = Some(Reformist);
+        coup.current_player_idx = 0;
+        assert!(coup.actions().contains(&Steal(0, 2)));
+
+        for num_players in 2..=6 {
+            play_random_game(Coup::with_config(GameConfig::reformation(num_players), &mut rng).unwrap());
+        }
+    }
+
+    #[test]
+    fn embezzle_challenge() {
+        // p0 has no duke, p1 has one
+        let mut coup = CoupBuilder::with_config(GameConfig::reformation(3))
+            .hand(0, Captain, Assassin)
+            .hand(1, Contessa, Duke)
+            .treasury_reserve(3)
+            .build()
+            .unwrap();
+
+        let proposal = Embezzle(0);
+        coup = try_action(coup, Box::new(move |a| *a == proposal));
+
+        coup = try_action(coup, Box::new(|a| *a == Challenge(1)));
+
+        // p0 shows their hand to prove they have no duke
+        coup = try_action(coup, Box::new(|a| *a == Disprove(0)));
+
+        // p1 loses the challenge
+        coup = try_action(coup, Box::new(|a| *a == Lose(1, 0)));
+
+        coup = try_action(coup, Box::new(|a| *a == Resolve(0)));
+
+        assert_eq!(coup.players[0].money, 5);
+        assert_eq!(coup.treasury_reserve, 0);
+
+        // a player with a duke can't prove they don't have one
+        coup.treasury_reserve = 1;
+        let proposal = Embezzle(1);
+        coup = try_action(coup, Box::new(move |a| *a == proposal));
+        coup = try_action(coup, Box::new(|a| *a == Challenge(2)));
+        assert_eq!(coup.actions(), vec![Lose(1, 1)]);
+    }
+
+    #[test]
+    fn inquisitor_examine() {
         let mut rng = thread_rng();
-        let mut coup = Coup::new(3, &mut rng);
+        // give p0 an inquisitor
+        let mut coup = CoupBuilder::with_config(GameConfig::inquisitor(3))
+            .hand(0, Inquisitor, Duke)
+            .hand(1, Captain, Contessa)
+            .build()
+            .unwrap();
+
+        assert!(coup.deck.iter().all(|&c| c != Ambassador));
+
+        let proposal = Examine(0, 1);
+        coup = try_action(coup, Box::new(move |a| *a == proposal));
+
+        coup = try_action(coup, Box::new(|a| *a == Pass(1)));
+        coup = try_action(coup, Box::new(|a| *a == Pass(2)));
+        coup = try_action(coup, Box::new(|a| *a == Resolve(0)));
 
+        // p1 chooses which card to show
+        assert_eq!(coup.actions(), vec![Show(1, 0), Show(1, 1)]);
+        coup = try_action(coup, Box::new(|a| *a == Show(1, 1)));
 
-        // give p0 an assassin
-        coup.players[0].influence_cards[0] = (Assassin, false);
+        // p0 now knows the card, p2 doesn't
+        coup = try_action(coup, Box::new(|a| *a == Release(0)));
+        for _ in 0..20 {
+            assert_eq!(coup.determine(&mut rng, 0).players[1].influence_cards[1], (Contessa, false));
+        }
+        assert!(coup.known_cards.contains(0, 1, 1));
 
-        // give p1 no contessa
-        coup.players[1].influence_cards[0] = (Duke, false);
-        coup.players[1].influence_cards[1] = (Duke, false);
+        find_action(&coup, Box::new(|a| *a == Income(1)));
+
+        // a forced swap means nobody knows the card anymore
+        coup.current_player_idx = 0;
+        let proposal = Examine(0, 1);
+        coup = try_action(coup, Box::new(move |a| *a == proposal));
+        coup = try_action(coup, Box::new(|a| *a == Pass(1)));
+        coup = try_action(coup, Box::new(|a| *a == Pass(2)));
+        coup = try_action(coup, Box::new(|a| *a == Resolve(0)));
+        coup = try_action(coup, Box::new(|a| *a == Show(1, 1)));
+        coup = try_action(coup, Box::new(|a| *a == ForceSwap(0)));
+        assert_eq!(coup.known_cards.iter().count(), 0);
+    }
+
+    #[test]
+    fn inquisitor_exchange_and_block() {
+        let mut rng = thread_rng();
+        let mut coup = CoupBuilder::with_config(GameConfig::inquisitor(3)).hand(0, Inquisitor, Duke).build().unwrap();
+
+        let proposal = Exchange(0);
+        coup = try_action(coup, Box::new(move |a| *a == proposal));
+        coup = try_action(coup, Box::new(|a| *a == Pass(1)));
+        coup = try_action(coup, Box::new(|a| *a == Pass(2)));
+        coup = try_action(coup, Box::new(|a| *a == Resolve(0)));
+
+        // only one card is drawn
+        assert_eq!(coup.exchange_cards.len(), 1);
+        coup = try_action(coup, Box::new(|a| *a == Keep(0, Duke, Some(Inquisitor))));
+        assert_eq!(coup.deck.len(), 9);
+
+        // the inquisitor blocks stealing instead of the ambassador
+        let mut coup = Coup::with_config(GameConfig::inquisitor(3), &mut rng).unwrap();
+        let proposal = Steal(0, 1);
+        coup = try_action(coup, Box::new(move |a| *a == proposal));
+        assert!(coup.actions().contains(&Block(1, Inquisitor)));
+        assert!(!coup.actions().contains(&Block(1, Ambassador)));
+
+        for num_players in 2..=6 {
+            play_random_game(Coup::with_config(GameConfig::inquisitor(num_players), &mut rng).unwrap());
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_actions() {
+        let mut rng = thread_rng();
+        let mut coup = Coup::new(3, &mut rng).unwrap();
+
+        assert_eq!(coup.apply_action(Income(1), &mut rng).unwrap_err(), CoupError::WrongPlayer(1, 0));
+        assert_eq!(coup.apply_action(Pass(0), &mut rng).unwrap_err(), CoupError::WrongPhase(Pass(0)));
+        assert_eq!(coup.apply_action(Action::Coup(0, 1), &mut rng).unwrap_err(), CoupError::InsufficientFunds(7, 2));
+        assert_eq!(coup.apply_action(Income(7), &mut rng).unwrap_err(), CoupError::NoSuchPlayer(7));
+
+        let proposal = Assassinate(0, 1);
+        assert_eq!(coup.apply_action(proposal, &mut rng).unwrap_err(), CoupError::InsufficientFunds(3, 2));
+
+        // can't propose on behalf of someone else
+        let proposal = Tax(1);
+        assert_eq!(coup.apply_action(proposal, &mut rng).unwrap_err(), CoupError::WrongPlayer(1, 0));
+
+        coup.players[1].influence_cards[0].1 = true;
+        coup.players[1].influence_cards[1].1 = true;
+        coup.eliminations.push((1, 0));
+        let proposal = Steal(0, 1);
+        assert_eq!(coup.apply_action(proposal, &mut rng).unwrap_err(), CoupError::DeadTarget(1));
+
+        coup.players[0].money = 7;
+        coup.players[2].influence_cards[1].1 = true;
+        coup = try_action(coup, Box::new(|a| *a == Action::Coup(0, 2)));
+        assert_eq!(coup.apply_action(Lose(2, 5), &mut rng).unwrap_err(), CoupError::BadCardIndex(2, 5));
+        assert_eq!(coup.apply_action(Lose(2, 1), &mut rng).unwrap_err(), CoupError::BadCardIndex(2, 1));
+
+        coup = try_action(coup, Box::new(|a| *a == Lose(2, 0)));
+        assert_eq!(coup.apply_action(Income(0), &mut rng).unwrap_err(), CoupError::GameOver);
+    }
+
+    #[test]
+    fn random_actions_never_panic() {
+        let mut rng = thread_rng();
+
+        let configs = [GameConfig::official(4), GameConfig::reformation(4), GameConfig::inquisitor(4), GameConfig::two_player()];
+        for config in configs {
+            let mut coup = Coup::with_config(config, &mut rng).unwrap();
+
+            for _ in 0..5000 {
+                // anything goes, most of these are nonsense
+                let action = random_action(&mut rng);
+
+                if let Ok(next) = coup.apply_action(action, &mut rng) {
+                    coup = next;
+                }
+
+                // keep the game going with legal moves too
+                let actions = coup.actions();
+                if coup.winner().is_some() {
+                    coup = Coup::with_config(config, &mut rng).unwrap();
+                } else {
+                    let action = actions[rng.gen_range(0..actions.len())];
+                    coup = coup.apply_action(action, &mut rng).unwrap();
+                }
+            }
+        }
+    }
+
+    fn random_action<R: Rng>(rng: &mut R) -> Action {
+        let p = rng.gen_range(0..6);
+        let n = rng.gen_range(0..6);
+        let character = [Duke, Assassin, Captain, Ambassador, Contessa, Inquisitor][rng.gen_range(0..6)];
+
+        match rng.gen_range(0..23) {
+            0 => Income(p),
+            1 => ForeignAid(p),
+            2 => Tax(p),
+            3 => Assassinate(p, n),
+            4 => Action::Coup(p, n),
+            5 => Steal(p, n),
+            6 => Exchange(p),
+            7 => Block(p, character),
+            8 => Relent(p),
+            9 => Challenge(p),
+            10 => Lose(p, n),
+            11 => Reveal(p, n),
+            12 => Pass(p),
+            13 => Resolve(p),
+            14 => Keep(p, character, if rng.gen_bool(0.5) { Some(Duke) } else { None }),
+            15 => ChooseInfluence(p, character),
+            16 => Convert(p, n),
+            17 => Embezzle(p),
+            18 => Disprove(p),
+            19 => Examine(p, n),
+            20 => Show(p, n),
+            21 => ForceSwap(p),
+            _ => Release(p),
+        }
+    }
+
+    #[test]
+    fn public_accessors() {
+        let mut coup = CoupBuilder::new(3).cards(1, &[(Captain, true), (Duke, false)]).build().unwrap();
+
+        assert_eq!(coup.num_players(), 3);
+        assert_eq!(coup.coins(0), 2);
+        assert_eq!(coup.deck_size(), 9);
+        assert_eq!(coup.revealed_cards(1).collect::<Vec<_>>(), vec![Captain]);
+        assert_eq!(coup.num_live_cards(1), 1);
+
+        // only the owner can see their live card
+        assert_eq!(coup.cards_visible_to(1, 1), vec![CardView::Revealed(Captain), CardView::Live(Duke)]);
+        assert_eq!(coup.cards_visible_to(0, 1), vec![CardView::Revealed(Captain), CardView::Hidden]);
+
+        let proposal = Tax(0);
+        coup = try_action(coup, Box::new(move |a| *a == proposal));
+
+        assert_eq!(coup.state(), &State::AwaitingProposalResponse(2));
+        assert_eq!(coup.proposal(), Some(&Tax(0)));
+        assert_eq!(coup.current_player_idx(), 0);
+        assert_eq!(coup.active_player_idx(), 1);
+
+        coup = try_action(coup, Box::new(|a| *a == Challenge(1)));
+        assert_eq!(coup.state(), &State::AwaitingChallengedProposalResponse(1));
+        assert_eq!(coup.active_player_idx(), 0);
+    }
+
+    #[test]
+    fn complete_game() {
+        // give p0 an assassin and p1 no contessa
+        let mut coup = CoupBuilder::new(3).hand(0, Assassin, Captain).hand(1, Duke, Duke).build().unwrap();
 
         // income round
         coup = try_action(coup, Box::new(|a| *a == Income(0)));
@@ -671,7 +1826,7 @@ mod tests {
         coup = try_action(coup, Box::new(|a| *a == Income(2)));
 
         // assassinate
-        let assassinate_proposal = Action::Propose(0, Box::new(Assassinate(0, 1)));
+        let assassinate_proposal = Assassinate(0, 1);
         coup = try_action(coup, Box::new(move |a| *a == assassinate_proposal));
 
         // p1 challenges
@@ -700,7 +1855,7 @@ mod tests {
         let mut rng = thread_rng();
         let mut num_actions: Vec<usize> = Vec::new();
         for _ in 0..1000 {
-            let mut coup = black_box(Coup::new(4, &mut rng));
+            let mut coup = black_box(Coup::new(4, &mut rng).unwrap());
             for _ in 0..1000 {
                 let mut actions = coup.actions();
                 num_actions.push(actions.len());
@@ -723,16 +1878,8 @@ mod tests {
 
     #[test]
     fn double_assassinate() {
-        let mut rng = thread_rng();
-
-        let mut coup = Coup::new(3, &mut rng);
-
-        // give p0 an assassin
-        coup.players[0].influence_cards[0] = (Assassin, false);
-
-        // give p1 no contessa
-        coup.players[1].influence_cards[0] = (Duke, false);
-        coup.players[1].influence_cards[1] = (Duke, false);
+        // give p0 an assassin and p1 no contessa
+        let mut coup = CoupBuilder::new(3).hand(0, Assassin, Captain).hand(1, Duke, Duke).build().unwrap();
 
         // income round
         coup = try_action(coup, Box::new(|a| *a == Income(0)));
@@ -740,7 +1887,7 @@ mod tests {
         coup = try_action(coup, Box::new(|a| *a == Income(2)));
 
         // assassinate
-        let assassinate_proposal = Action::Propose(0, Box::new(Assassinate(0, 1)));
+        let assassinate_proposal = Assassinate(0, 1);
         coup = try_action(coup, Box::new(move |a| *a == assassinate_proposal));
 
         // p1 challenges
@@ -764,15 +1911,8 @@ mod tests {
 
     #[test]
     fn normal_assassinate() {
-        let mut rng = thread_rng();
-        let mut coup = Coup::new(3, &mut rng);
-
-        // give p0 an assassin
-        coup.players[0].influence_cards[0] = (Assassin, false);
-
-        // give p1 no contessa
-        coup.players[1].influence_cards[0] = (Duke, false);
-        coup.players[1].influence_cards[1] = (Duke, false);
+        // give p0 an assassin and p1 no contessa
+        let mut coup = CoupBuilder::new(3).hand(0, Assassin, Captain).hand(1, Duke, Duke).build().unwrap();
 
         // income round
         coup = try_action(coup, Box::new(|a| *a == Income(0)));
@@ -782,7 +1922,7 @@ mod tests {
         assert_eq!(coup.players[0].money, 3);
 
         // assassinate
-        let assassinate_proposal = Action::Propose(0, Box::new(Assassinate(0, 1)));
+        let assassinate_proposal = Assassinate(0, 1);
         coup = try_action(coup, Box::new(move |a| *a == assassinate_proposal));
 
         // should instantly be out of money
@@ -801,19 +1941,11 @@ mod tests {
 
     #[test]
     fn test_steal() {
-        let mut rng = thread_rng();
-        let mut coup = Coup::new(3, &mut rng);
-
-        // give p0 a captain
-        coup.players[0].influence_cards[0] = (Captain, false);
-        coup.players[0].influence_cards[1] = (Duke, false);
-
-        // give p2 an ambassador
-        coup.players[2].influence_cards[0] = (Ambassador, false);
-        coup.players[2].influence_cards[1] = (Duke, false);
+        // give p0 a captain and p2 an ambassador
+        let mut coup = CoupBuilder::new(3).hand(0, Captain, Duke).hand(2, Ambassador, Duke).build().unwrap();
 
         // steal from p2
-        let proposal = Action::Propose(0, Box::new(Steal(0, 2)));
+        let proposal = Steal(0, 2);
         coup = try_action(coup, Box::new(move |a| *a == proposal));
 
         // p1 can't block - it's not targeting them
@@ -832,20 +1964,11 @@ mod tests {
 
     #[test]
     fn test_steal_block() {
-        let mut rng = thread_rng();
-        let mut coup = Coup::new(3, &mut rng);
-
-
-        // give p0 a captain
-        coup.players[0].influence_cards[0] = (Captain, false);
-        coup.players[0].influence_cards[1] = (Duke, false);
-
-        // give p2 an ambassador
-        coup.players[2].influence_cards[0] = (Ambassador, false);
-        coup.players[2].influence_cards[1] = (Duke, false);
+        // give p0 a captain and p2 an ambassador
+        let mut coup = CoupBuilder::new(3).hand(0, Captain, Duke).hand(2, Ambassador, Duke).build().unwrap();
 
         // steal from p2
-        let proposal = Action::Propose(0, Box::new(Steal(0, 2)));
+        let proposal = Steal(0, 2);
         coup = try_action(coup, Box::new(move |a| *a == proposal));
 
         // p1 can't block - it's not targeting them
@@ -880,20 +2003,11 @@ mod tests {
 
     #[test]
     fn test_steal_challenge() {
-        let mut rng = thread_rng();
-        let mut coup = Coup::new(3, &mut rng);
-
-
-        // give p0 a captain
-        coup.players[0].influence_cards[0] = (Captain, false);
-        coup.players[0].influence_cards[1] = (Duke, false);
-
-        // give p2 an ambassador
-        coup.players[2].influence_cards[0] = (Ambassador, false);
-        coup.players[2].influence_cards[1] = (Duke, false);
+        // give p0 a captain and p2 an ambassador
+        let mut coup = CoupBuilder::new(3).hand(0, Captain, Duke).hand(2, Ambassador, Duke).build().unwrap();
 
         // steal from p2
-        let proposal = Action::Propose(0, Box::new(Steal(0, 2)));
+        let proposal = Steal(0, 2);
         coup = try_action(coup, Box::new(move |a| *a == proposal));
 
         // p1 can't block - it's not targeting them - but they can challenge
@@ -915,10 +2029,47 @@ mod tests {
         assert_eq!(coup.players[2].money, 0);
     }
 
+    #[test]
+    fn test_exchange() {
+        // give p0 an ambassador
+        let mut coup = CoupBuilder::new(3).hand(0, Ambassador, Duke).build().unwrap();
+
+        let proposal = Exchange(0);
+        coup = try_action(coup, Box::new(move |a| *a == proposal));
+
+        coup = try_action(coup, Box::new(|a| *a == Pass(1)));
+        coup = try_action(coup, Box::new(|a| *a == Pass(2)));
+
+        coup = try_action(coup, Box::new(|a| *a == Resolve(0)));
+
+        // two cards are drawn and only choices to keep cards are available
+        assert_eq!(coup.exchange_cards.len(), 2);
+        assert_eq!(coup.deck.len(), 7);
+        assert!(coup.actions().iter().all(|a| matches!(a, Keep(0, _, Some(_)))));
+
+        // the kept cards can be given in either order
+        assert!(coup.is_legal(&Keep(0, Ambassador, Some(Duke))));
+        let reversed = coup.apply_deterministic(Keep(0, Ambassador, Some(Duke))).unwrap();
+        assert_eq!(reversed, coup.apply_deterministic(Keep(0, Duke, Some(Ambassador))).unwrap());
+        assert!(reversed.history().iter().any(|event| event.kind == EventKind::Action(Keep(0, Duke, Some(Ambassador)))));
+        assert!(coup.apply_deterministic("0:keep:ambassador:duke".parse().unwrap()).is_ok());
+
+        // keeping the original hand is always possible
+        coup = try_action(coup, Box::new(|a| *a == Keep(0, Duke, Some(Ambassador))));
+
+        // the two drawn cards went back into the deck
+        assert!(coup.exchange_cards.is_empty());
+        assert_eq!(coup.deck.len(), 9);
+        assert_eq!(*coup.players[0].influence_cards, [(Duke, false), (Ambassador, false)]);
+
+        // next action should be player 1 choice
+        find_action(&coup, Box::new(|a| *a == Income(1)));
+    }
+
     #[test]
     fn test_coup() {
         let mut rng = thread_rng();
-        let mut coup = Coup::new(3, &mut rng);
+        let mut coup = Coup::new(3, &mut rng).unwrap();
 
 
         // give p0 $10
@@ -943,20 +2094,11 @@ mod tests {
 
     #[test]
     fn test_steal_block_challenge() {
-        let mut rng = thread_rng();
-        let mut coup = Coup::new(4, &mut rng);
-
-
-        // give p0 a captain
-        coup.players[0].influence_cards[0] = (Captain, false);
-        coup.players[0].influence_cards[1] = (Duke, false);
-
-        // give p2 an ambassador
-        coup.players[2].influence_cards[0] = (Ambassador, false);
-        coup.players[2].influence_cards[1] = (Duke, false);
+        // give p0 a captain and p2 an ambassador
+        let mut coup = CoupBuilder::new(4).hand(0, Captain, Duke).hand(2, Ambassador, Duke).build().unwrap();
 
         // steal from p2
-        let proposal = Action::Propose(0, Box::new(Steal(0, 2)));
+        let proposal = Steal(0, 2);
         coup = try_action(coup, Box::new(move |a| *a == proposal));
 
         // p1 can't block - it's not targeting them
@@ -998,20 +2140,11 @@ mod tests {
 
     #[test]
     fn test_steal_block_challenge_2() {
-        let mut rng = thread_rng();
-        let mut coup = Coup::new(4, &mut rng);
-
-
-        // give p0 a captain
-        coup.players[0].influence_cards[0] = (Captain, false);
-        coup.players[0].influence_cards[1] = (Duke, false);
-
-        // give p2 an ambassador
-        coup.players[2].influence_cards[0] = (Ambassador, false);
-        coup.players[2].influence_cards[1] = (Duke, false);
+        // give p0 a captain and p2 an ambassador
+        let mut coup = CoupBuilder::new(4).hand(0, Captain, Duke).hand(2, Ambassador, Duke).build().unwrap();
 
         // steal from p2
-        let proposal = Action::Propose(0, Box::new(Steal(0, 2)));
+        let proposal = Steal(0, 2);
         coup = try_action(coup, Box::new(move |a| *a == proposal));
 
         // p1 can't block - it's not targeting them
@@ -1044,10 +2177,32 @@ mod tests {
         assert_eq!(coup.players[2].money, 2);
     }
 
+    #[test]
+    fn test_steal_block_bluff_challenged() {
+        // p2 has no captain to block with
+        let mut coup = CoupBuilder::new(3).hand(0, Captain, Duke).hand(2, Contessa, Duke).build().unwrap();
+
+        coup = try_action(coup, Box::new(|a| *a == Steal(0, 2)));
+        coup = try_action(coup, Box::new(|a| *a == Pass(1)));
+        coup = try_action(coup, Box::new(|a| *a == Block(2, Captain)));
+        coup = try_action(coup, Box::new(|a| *a == Pass(1)));
+        coup = try_action(coup, Box::new(|a| *a == Challenge(0)));
+
+        // p2 can't prove the block, so it falls through and the steal goes ahead
+        coup = try_action(coup, Box::new(|a| *a == Lose(2, 0)));
+        assert_eq!(coup.state, State::ResolveProposal);
+        assert_eq!(coup.proposal_blocked_with, None);
+        assert_eq!(coup.validate(), Ok(()));
+
+        coup = try_action(coup, Box::new(|a| *a == Resolve(0)));
+        assert_eq!(coup.players[0].money, 4);
+        assert_eq!(coup.players[2].money, 0);
+    }
+
     #[test]
     fn next_actor() {
         let mut rng = thread_rng();
-        let mut coup = Coup::new(4, &mut rng);
+        let mut coup = Coup::new(4, &mut rng).unwrap();
 
         assert_eq!(coup.next_prio_player_idx(), 1);
 
@@ -1064,7 +2219,7 @@ mod tests {
     #[test]
     fn other_players() {
         let mut rng = thread_rng();
-        let coup = Coup::new(4, &mut rng);
+        let coup = Coup::new(4, &mut rng).unwrap();
 
         assert_eq!(coup.other_player_indexes(0)[0], 1);
         assert_eq!(coup.other_player_indexes(0)[1], 2);
@@ -1077,10 +2232,43 @@ mod tests {
         assert_eq!(coup.other_player_indexes(1).len(), 3);
 
         let mut rng = thread_rng();
-        let coup = Coup::new(3, &mut rng);
+        let coup = Coup::new(3, &mut rng).unwrap();
 
         assert_eq!(coup.other_player_indexes(1)[0], 2);
         assert_eq!(coup.other_player_indexes(1)[1], 0);
         assert_eq!(coup.other_player_indexes(1).len(), 2);
     }
+
+    #[test]
+    fn action_queries_agree() {
+        let mut rng = thread_rng();
+        let configs = [GameConfig::official(5), GameConfig::reformation(4), GameConfig::inquisitor(4), GameConfig::two_player()];
+        let mut buffer = Vec::new();
+
+        for config in configs {
+            for _ in 0..20 {
+                let mut coup = Coup::with_config(config, &mut rng).unwrap();
+
+                while coup.winner().is_none() && coup.turn < 200 {
+                    let actions = coup.actions();
+                    coup.actions_into(&mut buffer);
+                    assert_eq!(buffer, actions);
+                    assert_eq!(coup.num_actions(), actions.len());
+                    assert!(actions.iter().all(|action| coup.is_legal(action)));
+                    assert!(!coup.is_legal(&Income(coup.players.len())));
+                    for player_idx in coup.players_indexes() {
+                        // anyone still in can leave, once everyone has a hand
+                        let selecting = matches!(coup.state, State::AwaitingInfluenceSelection(_));
+                        let leave = Action::Leave(player_idx, Departure::Concede);
+                        assert_eq!(coup.is_legal(&leave), !selecting && !coup.is_player_dead(player_idx));
+                    }
+                    assert_eq!(coup.num_actions() == 0, coup.random_action(&mut rng).is_none());
+
+                    let action = coup.random_action(&mut rng).unwrap();
+                    assert!(actions.contains(&action));
+                    coup = coup.apply_action(action, &mut rng).unwrap();
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,207 @@
+// a short text form for actions and characters, for typing them in and writing them to logs
+//
+// an action is the acting player, a separator and a verb, then any arguments split by ':'
+// - actions aimed at another player use '>' as the separator, everything else uses ':'
+// - arguments are a target player, a card index or a character, depending on the verb
+//
+//   0:income            0:foreign-aid       0:tax               0>assassinate:2
+//   0>coup:2            0>steal:2           0:exchange          2:block:contessa
+//   1:relent            1:challenge         1:lose:0            0:reveal:1
+//   1:pass              0:resolve           0:keep:duke:captain 0:keep:duke
+//   0:choose:duke       0>convert:2         0:embezzle          0:disprove
+//   0>examine:2         2:show:1            0:force-swap        0:release
+//   1:leave:concede     1:leave:timeout     1:leave:removed
+//
+// characters are their lowercase names - duke, assassin, captain, ambassador, contessa and inquisitor
+// parsing ignores case and surrounding whitespace, and takes either separator after the player
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::{Action, Character, CoupError, Departure};
+
+impl Display for Character {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Character::Duke => "duke",
+            Character::Assassin => "assassin",
+            Character::Captain => "captain",
+            Character::Ambassador => "ambassador",
+            Character::Contessa => "contessa",
+            Character::Inquisitor => "inquisitor",
+        })
+    }
+}
+
+impl FromStr for Character {
+    type Err = CoupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "duke" => Ok(Character::Duke),
+            "assassin" => Ok(Character::Assassin),
+            "captain" => Ok(Character::Captain),
+            "ambassador" => Ok(Character::Ambassador),
+            "contessa" => Ok(Character::Contessa),
+            "inquisitor" => Ok(Character::Inquisitor),
+            _ => Err(CoupError::InvalidNotation(format!("\"{s}\" isn't a character"))),
+        }
+    }
+}
+
+impl Display for Departure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Departure::Concede => "concede",
+            Departure::Timeout => "timeout",
+            Departure::Removed => "removed",
+        })
+    }
+}
+
+impl FromStr for Departure {
+    type Err = CoupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "concede" => Ok(Departure::Concede),
+            "timeout" => Ok(Departure::Timeout),
+            "removed" => Ok(Departure::Removed),
+            _ => Err(CoupError::InvalidNotation(format!("\"{s}\" isn't a way to leave"))),
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let player_idx = self.player_idx();
+
+        match *self {
+            Action::Income(_) => write!(f, "{player_idx}:income"),
+            Action::ForeignAid(_) => write!(f, "{player_idx}:foreign-aid"),
+            Action::Tax(_) => write!(f, "{player_idx}:tax"),
+            Action::Assassinate(_, target_player_idx) => write!(f, "{player_idx}>assassinate:{target_player_idx}"),
+            Action::Coup(_, target_player_idx) => write!(f, "{player_idx}>coup:{target_player_idx}"),
+            Action::Steal(_, target_player_idx) => write!(f, "{player_idx}>steal:{target_player_idx}"),
+            Action::Exchange(_) => write!(f, "{player_idx}:exchange"),
+            Action::Block(_, character) => write!(f, "{player_idx}:block:{character}"),
+            Action::Relent(_) => write!(f, "{player_idx}:relent"),
+            Action::Challenge(_) => write!(f, "{player_idx}:challenge"),
+            Action::Lose(_, card_idx) => write!(f, "{player_idx}:lose:{card_idx}"),
+            Action::Reveal(_, card_idx) => write!(f, "{player_idx}:reveal:{card_idx}"),
+            Action::Pass(_) => write!(f, "{player_idx}:pass"),
+            Action::Resolve(_) => write!(f, "{player_idx}:resolve"),
+            Action::Keep(_, first, None) => write!(f, "{player_idx}:keep:{first}"),
+            Action::Keep(_, first, Some(second)) => write!(f, "{player_idx}:keep:{first}:{second}"),
+            Action::ChooseInfluence(_, character) => write!(f, "{player_idx}:choose:{character}"),
+            Action::Convert(_, target_player_idx) => write!(f, "{player_idx}>convert:{target_player_idx}"),
+            Action::Embezzle(_) => write!(f, "{player_idx}:embezzle"),
+            Action::Disprove(_) => write!(f, "{player_idx}:disprove"),
+            Action::Examine(_, target_player_idx) => write!(f, "{player_idx}>examine:{target_player_idx}"),
+            Action::Show(_, card_idx) => write!(f, "{player_idx}:show:{card_idx}"),
+            Action::ForceSwap(_) => write!(f, "{player_idx}:force-swap"),
+            Action::Release(_) => write!(f, "{player_idx}:release"),
+            Action::Leave(_, departure) => write!(f, "{player_idx}:leave:{departure}"),
+        }
+    }
+}
+
+fn parse_idx(s: &str, what: &str) -> Result<usize, CoupError> {
+    s.trim().parse().map_err(|_| CoupError::InvalidNotation(format!("\"{s}\" isn't a {what}")))
+}
+
+impl FromStr for Action {
+    type Err = CoupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CoupError::InvalidNotation(format!("\"{s}\" isn't an action"));
+
+        let lowercase = s.trim().to_lowercase();
+        let (player, rest) = lowercase.split_once([':', '>']).ok_or_else(invalid)?;
+        let player_idx = parse_idx(player, "player")?;
+
+        let parts: Vec<&str> = rest.split(':').map(str::trim).collect();
+        let action = match parts[..] {
+            ["income"] => Action::Income(player_idx),
+            ["foreign-aid"] => Action::ForeignAid(player_idx),
+            ["tax"] => Action::Tax(player_idx),
+            ["assassinate", target] => Action::Assassinate(player_idx, parse_idx(target, "player")?),
+            ["coup", target] => Action::Coup(player_idx, parse_idx(target, "player")?),
+            ["steal", target] => Action::Steal(player_idx, parse_idx(target, "player")?),
+            ["exchange"] => Action::Exchange(player_idx),
+            ["block", character] => Action::Block(player_idx, character.parse()?),
+            ["relent"] => Action::Relent(player_idx),
+            ["challenge"] => Action::Challenge(player_idx),
+            ["lose", card] => Action::Lose(player_idx, parse_idx(card, "card index")?),
+            ["reveal", card] => Action::Reveal(player_idx, parse_idx(card, "card index")?),
+            ["pass"] => Action::Pass(player_idx),
+            ["resolve"] => Action::Resolve(player_idx),
+            ["keep", first] => Action::Keep(player_idx, first.parse()?, None),
+            ["keep", first, second] => Action::Keep(player_idx, first.parse()?, Some(second.parse()?)),
+            ["choose", character] => Action::ChooseInfluence(player_idx, character.parse()?),
+            ["convert", target] => Action::Convert(player_idx, parse_idx(target, "player")?),
+            ["embezzle"] => Action::Embezzle(player_idx),
+            ["disprove"] => Action::Disprove(player_idx),
+            ["examine", target] => Action::Examine(player_idx, parse_idx(target, "player")?),
+            ["show", card] => Action::Show(player_idx, parse_idx(card, "card index")?),
+            ["force-swap"] => Action::ForceSwap(player_idx),
+            ["release"] => Action::Release(player_idx),
+            ["leave", departure] => Action::Leave(player_idx, departure.parse()?),
+            _ => return Err(invalid()),
+        };
+
+        Ok(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use crate::{Action, Character, Coup, CoupError, Departure, GameConfig};
+    use crate::Character::{Captain, Contessa, Duke};
+
+    #[test]
+    fn round_trips() {
+        let actions = [
+            Action::Income(0), Action::ForeignAid(1), Action::Tax(2), Action::Assassinate(0, 2), Action::Coup(9, 3),
+            Action::Steal(1, 0), Action::Exchange(0), Action::Block(2, Contessa), Action::Relent(1), Action::Challenge(1),
+            Action::Lose(1, 0), Action::Reveal(0, 1), Action::Pass(3), Action::Resolve(0), Action::Keep(0, Duke, Some(Captain)),
+            Action::Keep(0, Duke, None), Action::ChooseInfluence(1, Duke), Action::Convert(0, 2), Action::Embezzle(0),
+            Action::Disprove(0), Action::Examine(0, 2), Action::Show(2, 1), Action::ForceSwap(0), Action::Release(0),
+            Action::Leave(1, Departure::Concede), Action::Leave(2, Departure::Timeout), Action::Leave(0, Departure::Removed),
+        ];
+
+        for action in actions {
+            assert_eq!(action.to_string().parse::<Action>().unwrap(), action);
+        }
+
+        for character in [Duke, Captain, Contessa, Character::Assassin, Character::Ambassador, Character::Inquisitor] {
+            assert_eq!(character.to_string().parse::<Character>().unwrap(), character);
+        }
+
+        // everything a real game can come up with
+        let mut rng = thread_rng();
+        for config in [GameConfig::reformation(4), GameConfig::inquisitor(4), GameConfig::two_player()] {
+            let mut coup = Coup::with_config(config, &mut rng).unwrap();
+            while coup.winner().is_none() && coup.turn() < 100 {
+                for action in coup.actions() {
+                    assert_eq!(action.to_string().parse::<Action>().unwrap(), action);
+                }
+                coup = coup.apply_action(coup.random_action(&mut rng).unwrap(), &mut rng).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn parses() {
+        assert_eq!("0:tax".parse::<Action>().unwrap(), Action::Tax(0));
+        assert_eq!("0>assassinate:2".parse::<Action>().unwrap(), Action::Assassinate(0, 2));
+        assert_eq!(" 0:Assassinate:2 ".parse::<Action>().unwrap(), Action::Assassinate(0, 2));
+        assert_eq!("2:block:contessa".parse::<Action>().unwrap(), Action::Block(2, Contessa));
+        assert_eq!("1:lose:0".parse::<Action>().unwrap(), Action::Lose(1, 0));
+        assert_eq!(Action::Keep(0, Duke, Some(Captain)).to_string(), "0:keep:duke:captain");
+
+        for invalid in ["", "tax", "0:", "0:taxes", "x:tax", "0:tax:1", "0>steal", "0>steal:me", "2:block:queen", "1:leave", "1:leave:rage", "0:keep:duke:captain:contessa"] {
+            assert!(matches!(invalid.parse::<Action>(), Err(CoupError::InvalidNotation(_))), "{invalid}");
+        }
+    }
+}
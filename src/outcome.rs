@@ -0,0 +1,260 @@
+// how a game turned out for everyone at the table, not just who won - for scoring multi-player games,
+// tournament tables and ai that would rather come second than last
+// players are placed by when they went out, so the last one standing is first and the first one out is last
+
+use serde::{Deserialize, Serialize};
+use crate::{Action, Character, Coup, Draw, GameConfig, Player, State, MAX_PENDING_DRAWS};
+use crate::config::{MAX_CARDS, MAX_EXCHANGE_DRAW, MAX_PLAYERS};
+use crate::history::{EventKind, History};
+use crate::inline_vec::InlineVec;
+use crate::known_cards::KnownCards;
+use crate::zobrist::Zobrist;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameOutcome {
+    // the winner, or everyone still in when the turn limit ran out, who share first place
+    pub survivors: Vec<usize>,
+    // (player, turn) for everyone else, in the order they went out
+    pub eliminations: Vec<(usize, usize)>,
+}
+
+impl GameOutcome {
+    pub fn winner(&self) -> Option<usize> {
+        match self.survivors[..] {
+            [winner] => Some(winner),
+            _ => None,
+        }
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.survivors.len() > 1
+    }
+
+    // everyone grouped by where they finished, best first
+    pub fn placements(&self) -> Vec<Vec<usize>> {
+        let mut placements = vec![self.survivors.clone()];
+        placements.extend(self.eliminations.iter().rev().map(|&(player_idx, _)| vec![player_idx]));
+        placements
+    }
+
+    // how many players finished ahead of this one, so 0 for first
+    pub fn place(&self, player_idx: usize) -> Option<usize> {
+        if self.survivors.contains(&player_idx) {
+            return Some(0);
+        }
+
+        self.eliminations
+            .iter()
+            .position(|&(eliminated_idx, _)| eliminated_idx == player_idx)
+            .map(|elimination_idx| self.survivors.len() + self.eliminations.len() - 1 - elimination_idx)
+    }
+
+    // 1 for winning outright down to 0 for going out first, with players who share a place
+    // splitting the places between them - a utility for ai that cares about more than winning
+    pub fn score(&self, player_idx: usize) -> f32 {
+        let num_players = self.survivors.len() + self.eliminations.len();
+        let Some(place) = self.place(player_idx) else {
+            return 0.0;
+        };
+
+        let num_sharing = if place == 0 { self.survivors.len() } else { 1 };
+        let average_place = place as f32 + (num_sharing - 1) as f32 / 2.0;
+        1.0 - average_place / (num_players - 1).max(1) as f32
+    }
+}
+
+impl Coup {
+    // (player, turn) for everyone who's been knocked out, in the order they went
+    pub fn eliminations(&self) -> &[(usize, usize)] {
+        &self.eliminations
+    }
+
+    // the turn the player went out on, if they're out
+    pub fn eliminated_on(&self, player_idx: usize) -> Option<usize> {
+        self.eliminations
+            .iter()
+            .find(|&&(eliminated_idx, _)| eliminated_idx == player_idx)
+            .map(|&(_, turn)| turn)
+    }
+
+    // the turn limit house rule ran out before anyone won
+    pub fn is_drawn(&self) -> bool {
+        self.config.turn_limit.is_some_and(|turn_limit| self.turn >= turn_limit as usize) && self.winner().is_none()
+    }
+
+    // won or drawn, either way nobody can act any more
+    pub fn is_over(&self) -> bool {
+        self.winner().is_some() || self.is_drawn()
+    }
+
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        self.is_over().then(|| self.standings())
+    }
+
+    // the outcome if the game were stopped here, with everyone still in sharing first place
+    pub fn standings(&self) -> GameOutcome {
+        GameOutcome {
+            survivors: self.players_indexes().filter(|&player_idx| self.eliminated_on(player_idx).is_none()).collect(),
+            eliminations: self.eliminations.to_vec(),
+        }
+    }
+}
+
+// a game as it's read back - saves from before eliminations were kept don't have them, so they're
+// rebuilt from the history, with anyone out who isn't in it going out before the game began
+#[derive(Deserialize)]
+pub(crate) struct SavedCoup {
+    config: GameConfig,
+    turn: usize,
+    current_player_idx: usize,
+    deck: InlineVec<Character, MAX_CARDS>,
+    players: InlineVec<Player, { MAX_PLAYERS as usize }>,
+    state: State,
+    priority_player_idx: Option<usize>,
+    proposal: Option<Action>,
+    proposal_blocked_with: Option<Character>,
+    exchange_cards: InlineVec<Character, { MAX_EXCHANGE_DRAW as usize }>,
+    treasury_reserve: u8,
+    known_cards: KnownCards,
+    pending_draws: InlineVec<Draw, MAX_PENDING_DRAWS>,
+    #[serde(default)]
+    eliminations: Option<InlineVec<(usize, usize), { MAX_PLAYERS as usize }>>,
+    history: History,
+}
+
+impl From<SavedCoup> for Coup {
+    fn from(saved: SavedCoup) -> Self {
+        let mut game = Coup {
+            config: saved.config,
+            turn: saved.turn,
+            current_player_idx: saved.current_player_idx,
+            deck: saved.deck,
+            players: saved.players,
+            state: saved.state,
+            priority_player_idx: saved.priority_player_idx,
+            proposal: saved.proposal,
+            proposal_blocked_with: saved.proposal_blocked_with,
+            exchange_cards: saved.exchange_cards,
+            treasury_reserve: saved.treasury_reserve,
+            known_cards: saved.known_cards,
+            pending_draws: saved.pending_draws,
+            eliminations: saved.eliminations.unwrap_or_default(),
+            history: saved.history,
+            zobrist: Zobrist::default(),
+        };
+
+        if saved.eliminations.is_none() {
+            game.eliminations = game.rebuilt_eliminations();
+        }
+
+        game
+    }
+}
+
+impl Coup {
+    fn rebuilt_eliminations(&self) -> InlineVec<(usize, usize), { MAX_PLAYERS as usize }> {
+        // nobody is out while hands are being picked
+        if matches!(self.state, State::AwaitingInfluenceSelection(_)) {
+            return InlineVec::new();
+        }
+
+        let recorded: Vec<(usize, usize)> = self.history()
+            .iter()
+            .filter_map(|event| match event.kind {
+                EventKind::PlayerEliminated(player_idx) => Some((player_idx, event.turn)),
+                _ => None,
+            })
+            .collect();
+
+        let unrecorded = self.players_indexes()
+            .filter(|&player_idx| self.is_player_dead(player_idx))
+            .filter(|&player_idx| !recorded.iter().any(|&(eliminated_idx, _)| eliminated_idx == player_idx))
+            .map(|player_idx| (player_idx, 0));
+
+        unrecorded.chain(recorded.iter().copied()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Action, Coup, CoupBuilder, CoupError, GameConfig};
+    use crate::Character::{Captain, Contessa, Duke};
+
+    #[test]
+    fn places_players_by_when_they_went_out() {
+        let mut coup = CoupBuilder::new(3)
+            .cards(0, &[(Duke, true), (Contessa, false)])
+            .cards(1, &[(Duke, true), (Captain, false)])
+            .cards(2, &[(Duke, true), (Captain, false)])
+            .coins(0, 7)
+            .coins(1, 7)
+            .build()
+            .unwrap();
+
+        assert_eq!(coup.outcome(), None);
+
+        for action in [Action::Coup(0, 2), Action::Lose(2, 1), Action::Coup(1, 0), Action::Lose(0, 1)] {
+            coup = coup.apply_deterministic(action).unwrap();
+        }
+
+        assert_eq!(coup.eliminations(), &[(2, 0), (0, 1)]);
+        assert_eq!(coup.eliminated_on(0), Some(1));
+        assert_eq!(coup.eliminated_on(1), None);
+
+        let outcome = coup.outcome().unwrap();
+        assert_eq!(outcome.winner(), Some(1));
+        assert!(!outcome.is_draw());
+        assert_eq!(outcome.placements(), vec![vec![1], vec![0], vec![2]]);
+        assert_eq!([0, 1, 2].map(|player_idx| outcome.place(player_idx)), [Some(1), Some(0), Some(2)]);
+        assert_eq!([0, 1, 2].map(|player_idx| outcome.score(player_idx)), [0.5, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn rebuilds_eliminations_missing_from_old_saves() {
+        let mut coup = CoupBuilder::new(4)
+            .cards(1, &[(Duke, true), (Captain, false)])
+            .cards(2, &[(Duke, true), (Captain, false)])
+            .coins(0, 7)
+            .build()
+            .unwrap();
+
+        for action in [Action::Coup(0, 2), Action::Lose(2, 1), Action::Income(1)] {
+            coup = coup.apply_deterministic(action).unwrap();
+        }
+
+        let mut json = serde_json::to_value(&coup).unwrap();
+        json.as_object_mut().unwrap().remove("eliminations");
+
+        let loaded: Coup = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(loaded.eliminations(), &[(2, 0)]);
+        assert_eq!(loaded.validate(), Ok(()));
+        assert!(loaded.apply_deterministic(Action::Income(3)).is_ok());
+
+        // without a history to go by, anyone out went out before the game began
+        json["history"] = serde_json::json!([]);
+        json["players"][1]["influence_cards"][1][1] = serde_json::json!(true);
+        let loaded: Coup = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.eliminations(), &[(1, 0), (2, 0)]);
+        assert_eq!(loaded.validate(), Ok(()));
+    }
+
+    #[test]
+    fn turn_limit_draws_the_game() {
+        let mut config = GameConfig::official(3);
+        config.turn_limit = Some(2);
+
+        let mut coup = CoupBuilder::with_config(config).build().unwrap();
+        coup = coup.apply_deterministic(Action::Income(0)).unwrap();
+        assert!(!coup.is_over());
+
+        coup = coup.apply_deterministic(Action::Income(1)).unwrap();
+        assert!(coup.is_drawn());
+        assert_eq!(coup.apply_deterministic(Action::Income(2)).unwrap_err(), CoupError::GameOver);
+
+        let outcome = coup.outcome().unwrap();
+        assert_eq!(outcome.winner(), None);
+        assert!(outcome.is_draw());
+        assert_eq!(outcome.placements(), vec![vec![0, 1, 2]]);
+        assert_eq!(outcome.score(2), 0.5);
+    }
+}
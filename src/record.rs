@@ -0,0 +1,458 @@
+// a text record of a whole game, in the spirit of chess PGN - tag pairs for the header, then the moves
+//
+//   [Rules "official"]
+//   [Players "3"]
+//   [Seed "42"]
+//   [Player0 "alice"]
+//   [Result "1"]
+//   [Hands "duke captain, assassin contessa, ambassador duke"]
+//   [Deck "captain contessa ..."]
+//
+//   1. 0:tax 1:challenge 0:reveal:0=ambassador 1:lose:0 0:resolve
+//   2. 1:income
+//
+// moves are actions in the notation from notation.rs, each turn on its own numbered line
+// cards drawn because of a move are pinned after it with '=', so replaying never needs a random draw
+// the rules are one of official, reformation, inquisitor or two-player, or custom with a Config tag
+// holding the config as json - Seed, the player names and Result are optional
+// the result is the winner's index, "draw" when the turn limit ran out, or "*" while the game is going
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use rand::Rng;
+use crate::{Action, Character, Coup, CoupBuilder, CoupError, GameConfig, SetupMode, State};
+
+// an action and the cards it led to being drawn, in order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedMove {
+    pub action: Action,
+    pub draws: Vec<Character>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    Won(usize),
+    // the turn limit ran out before anyone won
+    Drawn,
+}
+
+impl GameResult {
+    // how the game has ended, if it has
+    pub fn of(game: &Coup) -> Option<Self> {
+        match game.winner() {
+            Some(winner) => Some(GameResult::Won(winner)),
+            None if game.is_drawn() => Some(GameResult::Drawn),
+            None => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+    pub config: GameConfig,
+    // whatever seed the game was dealt with, just for reference
+    pub seed: Option<u64>,
+    // by player index, empty names are left out of the record
+    pub names: Vec<String>,
+    // how the game ended, once it has
+    pub result: Option<GameResult>,
+    // the starting position - every hand, then the rest of the deck in order
+    pub hands: Vec<Vec<Character>>,
+    pub deck: Vec<Character>,
+    pub moves: Vec<RecordedMove>,
+}
+
+impl GameRecord {
+    // starts recording a game that hasn't had any moves made yet
+    pub fn new(start: &Coup) -> Result<Self, CoupError> {
+        let record = Self {
+            config: start.config,
+            seed: None,
+            names: Vec::new(),
+            result: None,
+            hands: start.players.iter().map(|player| player.influence_cards.iter().map(|card| card.0).collect()).collect(),
+            deck: start.deck.to_vec(),
+            moves: Vec::new(),
+        };
+
+        if record.start()? != *start {
+            return Err(CoupError::InvalidRecord("only a game that hasn't started yet can be recorded".to_string()));
+        }
+
+        Ok(record)
+    }
+
+    // the position the game started from
+    pub fn start(&self) -> Result<Coup, CoupError> {
+        let mut builder = CoupBuilder::with_config(self.config).deck(&self.deck);
+
+        if self.config.setup == SetupMode::TwoPlayer {
+            builder = builder.phase(State::AwaitingInfluenceSelection(0));
+        }
+
+        for (player_idx, hand) in self.hands.iter().enumerate() {
+            let cards: Vec<(Character, bool)> = hand.iter().map(|&card| (card, false)).collect();
+            builder = builder.cards(player_idx, &cards);
+        }
+
+        builder.build()
+    }
+
+    // applies the action to the game, drawing at random and recording what was drawn
+    pub fn play<R: Rng + Sized>(&mut self, game: &Coup, action: Action, rng: &mut R) -> Result<Coup, CoupError> {
+        let mut next = game.apply_deterministic(action)?;
+
+        let mut draws = Vec::new();
+        while next.is_chance_node() {
+            let character = next.deck[rng.gen_range(0..next.deck.len())];
+            next.apply_chance_mut(character)?;
+            draws.push(character);
+        }
+
+        self.moves.push(RecordedMove { action, draws });
+        self.result = GameResult::of(&next);
+
+        Ok(next)
+    }
+
+    // every position in the game, from the start to after the last move
+    pub fn replay(&self) -> Result<Vec<Coup>, CoupError> {
+        let mut game = self.start()?;
+        let mut states = vec![game.clone()];
+
+        for recorded_move in &self.moves {
+            game.apply_deterministic_mut(recorded_move.action)?;
+            for &character in &recorded_move.draws {
+                game.apply_chance_mut(character)?;
+            }
+
+            if game.is_chance_node() {
+                return Err(CoupError::ChancePending);
+            }
+
+            states.push(game.clone());
+        }
+
+        if self.result.is_some() && self.result != GameResult::of(&game) {
+            return Err(CoupError::InvalidRecord(format!("the record says {:?}, but the moves end with {:?}", self.result, GameResult::of(&game))));
+        }
+
+        Ok(states)
+    }
+
+    fn rules(&self) -> &str {
+        let num_players = self.config.num_players;
+
+        if self.config == GameConfig::official(num_players) {
+            "official"
+        } else if self.config == GameConfig::reformation(num_players) {
+            "reformation"
+        } else if self.config == GameConfig::inquisitor(num_players) {
+            "inquisitor"
+        } else if self.config == GameConfig::two_player() {
+            "two-player"
+        } else {
+            "custom"
+        }
+    }
+}
+
+// moves that can only be made at the start of a turn, so they start a new line
+fn starts_turn(action: &Action) -> bool {
+    action.is_proposal() || matches!(action, Action::Income(_) | Action::Coup(_, _) | Action::Convert(_, _))
+}
+
+fn write_cards(cards: &[Character]) -> String {
+    match cards {
+        [] => "-".to_string(),
+        _ => cards.iter().map(Character::to_string).collect::<Vec<_>>().join(" "),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let tag = |f: &mut Formatter<'_>, name: &str, value: &str| writeln!(f, "[{name} \"{}\"]", escape(value));
+
+        tag(f, "Rules", self.rules())?;
+        if self.rules() == "custom" {
+            tag(f, "Config", &serde_json::to_string(&self.config).map_err(|_| std::fmt::Error)?)?;
+        }
+        tag(f, "Players", &self.config.num_players.to_string())?;
+        if let Some(seed) = self.seed {
+            tag(f, "Seed", &seed.to_string())?;
+        }
+        for (player_idx, name) in self.names.iter().enumerate().filter(|(_, name)| !name.is_empty()) {
+            tag(f, &format!("Player{player_idx}"), name)?;
+        }
+        let result = match self.result {
+            Some(GameResult::Won(winner)) => winner.to_string(),
+            Some(GameResult::Drawn) => "draw".to_string(),
+            None => "*".to_string(),
+        };
+        tag(f, "Result", &result)?;
+        tag(f, "Hands", &self.hands.iter().map(|hand| write_cards(hand)).collect::<Vec<_>>().join(", "))?;
+        tag(f, "Deck", &write_cards(&self.deck))?;
+        writeln!(f)?;
+
+        let mut line = Vec::new();
+        let mut turn_number = 1;
+        for recorded_move in &self.moves {
+            if starts_turn(&recorded_move.action) && !line.is_empty() {
+                writeln!(f, "{turn_number}. {}", line.join(" "))?;
+                line.clear();
+                turn_number += 1;
+            }
+
+            let draws: Vec<String> = recorded_move.draws.iter().map(Character::to_string).collect();
+            match draws.is_empty() {
+                true => line.push(recorded_move.action.to_string()),
+                false => line.push(format!("{}={}", recorded_move.action, draws.join(","))),
+            }
+        }
+
+        if !line.is_empty() {
+            writeln!(f, "{turn_number}. {}", line.join(" "))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(reason: String) -> CoupError {
+    CoupError::InvalidNotation(reason)
+}
+
+fn parse_cards(s: &str) -> Result<Vec<Character>, CoupError> {
+    match s.trim() {
+        "-" => Ok(Vec::new()),
+        cards => cards.split_whitespace().map(str::parse).collect(),
+    }
+}
+
+// a number that has to fit the type it's read into
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, CoupError> {
+    value.parse().map_err(|_| invalid(format!("the value of {name} has to be a number that fits, not \"{value}\"")))
+}
+
+// [Name "value"]
+fn parse_tag(line: &str) -> Result<(&str, String), CoupError> {
+    let (name, value) = line.strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .and_then(|line| line.split_once(' '))
+        .ok_or_else(|| invalid(format!("\"{line}\" isn't a tag")))?;
+
+    let value = value.trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(|| invalid(format!("the value of {name} has to be quoted")))?;
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next().ok_or_else(|| invalid(format!("the value of {name} ends in an escape")))?),
+            c => unescaped.push(c),
+        }
+    }
+
+    Ok((name, unescaped))
+}
+
+impl FromStr for GameRecord {
+    type Err = CoupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = None;
+        let mut custom_config = None;
+        let mut num_players = None;
+        let mut seed = None;
+        let mut names: Vec<(usize, String)> = Vec::new();
+        let mut result = None;
+        let mut hands = None;
+        let mut deck = None;
+        let mut moves = Vec::new();
+
+        for line in s.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if line.starts_with('[') {
+                let (name, value) = parse_tag(line)?;
+
+                match name {
+                    "Rules" => rules = Some(value),
+                    "Config" => custom_config = Some(serde_json::from_str::<GameConfig>(&value).map_err(|error| invalid(error.to_string()))?),
+                    "Players" => num_players = Some(parse_number(name, &value)?),
+                    "Seed" => seed = Some(parse_number(name, &value)?),
+                    "Result" if value == "*" => result = None,
+                    "Result" if value == "draw" => result = Some(GameResult::Drawn),
+                    "Result" => result = Some(GameResult::Won(parse_number(name, &value)?)),
+                    "Hands" => hands = Some(value.split(',').map(parse_cards).collect::<Result<Vec<_>, _>>()?),
+                    "Deck" => deck = Some(parse_cards(&value)?),
+                    _ => {
+                        // anything else is for other tools
+                        if let Some(Ok(player_idx)) = name.strip_prefix("Player").map(str::parse::<usize>) {
+                            names.push((player_idx, value));
+                        }
+                    }
+                }
+
+                continue;
+            }
+
+            // turn numbers are just for reading
+            for token in line.split_whitespace().filter(|token| !token.ends_with('.')) {
+                let (action, draws) = match token.split_once('=') {
+                    Some((action, draws)) => (action, draws.split(',').map(str::parse).collect::<Result<Vec<_>, _>>()?),
+                    None => (token, Vec::new()),
+                };
+
+                moves.push(RecordedMove { action: action.parse()?, draws });
+            }
+        }
+
+        let num_players = num_players.ok_or_else(|| invalid("the record has no Players tag".to_string()))?;
+        let config = match rules.as_deref() {
+            Some("custom") => custom_config.ok_or_else(|| invalid("custom rules need a Config tag".to_string()))?,
+            Some(rules) => GameConfig::named(rules, num_players).ok_or_else(|| invalid(format!("\"{rules}\" aren't rules this knows")))?,
+            None => return Err(invalid("the record has no Rules tag".to_string())),
+        };
+
+        if config.num_players != num_players {
+            return Err(CoupError::InvalidRecord(format!("the rules are for {} players, not {num_players}", config.num_players)));
+        }
+
+        names.sort();
+        let mut record_names = Vec::new();
+        for (player_idx, name) in names {
+            if player_idx >= num_players as usize {
+                return Err(CoupError::NoSuchPlayer(player_idx));
+            }
+
+            record_names.resize(player_idx, String::new());
+            record_names.push(name);
+        }
+
+        let hands = hands.ok_or_else(|| invalid("the record has no Hands tag".to_string()))?;
+        if hands.len() != num_players as usize {
+            return Err(CoupError::InvalidRecord(format!("there are {} hands for {num_players} players", hands.len())));
+        }
+
+        Ok(Self {
+            config,
+            seed,
+            names: record_names,
+            result,
+            hands,
+            deck: deck.ok_or_else(|| invalid("the record has no Deck tag".to_string()))?,
+            moves,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use crate::{Action, Coup, CoupError, GameConfig};
+    use crate::record::{GameRecord, GameResult};
+
+    #[test]
+    fn replays_recorded_games() {
+        let mut rng = thread_rng();
+        let mut config = GameConfig::official(4);
+        config.starting_money = 5;
+        let configs = [GameConfig::official(3), GameConfig::reformation(4), GameConfig::inquisitor(5), GameConfig::two_player(), config];
+
+        for config in configs {
+            let mut coup = Coup::with_config(config, &mut rng).unwrap();
+            let mut record = GameRecord::new(&coup).unwrap();
+            record.seed = Some(7);
+            record.names = vec!["alice".to_string(), "\"bob\"".to_string()];
+
+            let mut states = vec![coup.clone()];
+            while coup.winner().is_none() && coup.turn() < 200 {
+                let action = coup.random_action(&mut rng).unwrap();
+                coup = record.play(&coup, action, &mut rng).unwrap();
+                states.push(coup.clone());
+            }
+
+            let text = record.to_string();
+            let loaded: GameRecord = text.parse().unwrap();
+            assert_eq!(loaded, record, "{text}");
+            assert_eq!(loaded.replay().unwrap(), states);
+        }
+    }
+
+    #[test]
+    fn records_draws() {
+        let mut rng = thread_rng();
+        let mut config = GameConfig::official(3);
+        config.turn_limit = Some(2);
+
+        let mut coup = Coup::with_config(config, &mut rng).unwrap();
+        let mut record = GameRecord::new(&coup).unwrap();
+        coup = record.play(&coup, Action::Income(0), &mut rng).unwrap();
+        assert_eq!(record.result, None);
+        assert!(record.to_string().contains("[Result \"*\"]"));
+
+        coup = record.play(&coup, Action::Income(1), &mut rng).unwrap();
+        assert!(coup.is_drawn());
+        assert_eq!(record.result, Some(GameResult::Drawn));
+
+        let text = record.to_string();
+        assert!(text.contains("[Result \"draw\"]"), "{text}");
+        let loaded: GameRecord = text.parse().unwrap();
+        assert_eq!(loaded, record);
+        assert_eq!(loaded.replay().unwrap().last(), Some(&coup));
+
+        let tampered: GameRecord = text.replace("[Result \"draw\"]", "[Result \"0\"]").parse().unwrap();
+        assert!(matches!(tampered.replay(), Err(CoupError::InvalidRecord(_))));
+    }
+
+    #[test]
+    fn reads_records() {
+        let text = r#"
+            [Rules "official"]
+            [Players "3"]
+            [Player1 "bob"]
+            [Result "*"]
+            [Event "club night"]
+            [Hands "duke captain, assassin contessa, ambassador duke"]
+            [Deck "duke assassin assassin captain captain ambassador ambassador contessa contessa"]
+
+            1. 0:tax 1:challenge 0:reveal:0=ambassador 1:lose:0 0:resolve
+            2. 1:income
+        "#;
+
+        let record: GameRecord = text.parse().unwrap();
+        assert_eq!(record.names, vec!["".to_string(), "bob".to_string()]);
+        assert_eq!(record.moves.len(), 6);
+        assert_eq!(record.moves[2].draws, vec![crate::Character::Ambassador]);
+
+        let states = record.replay().unwrap();
+        let last = states.last().unwrap();
+        assert_eq!(last.player(0).influence_cards()[0].0, crate::Character::Ambassador);
+        assert_eq!(last.coins(0), 5);
+        assert_eq!(last.coins(1), 3);
+
+        // drawing a card that isn't there
+        let tampered: GameRecord = text.replace("=ambassador", "=captain,duke").parse().unwrap();
+        assert_eq!(tampered.replay().unwrap_err(), CoupError::NoChancePending);
+        let tampered: GameRecord = text.replace("[Result \"*\"]", "[Result \"2\"]").parse().unwrap();
+        assert!(matches!(tampered.replay(), Err(CoupError::InvalidRecord(_))));
+        let tampered: GameRecord = text.replace("[Result \"*\"]", "[Result \"draw\"]").parse().unwrap();
+        assert!(matches!(tampered.replay(), Err(CoupError::InvalidRecord(_))));
+
+        assert!(matches!(text.replace("0:tax", "0:taxes").parse::<GameRecord>(), Err(CoupError::InvalidNotation(_))));
+        assert!(matches!("[Rules \"official\"]".parse::<GameRecord>(), Err(CoupError::InvalidNotation(_))));
+
+        // a player count that would wrap around to 3
+        assert!(matches!(text.replace("[Players \"3\"]", "[Players \"259\"]").parse::<GameRecord>(), Err(CoupError::InvalidNotation(_))));
+        assert!(matches!(text.replace("[Players \"3\"]", "[Players \"-3\"]").parse::<GameRecord>(), Err(CoupError::InvalidNotation(_))));
+
+        // only games that haven't started can be recorded
+        let coup = Coup::new(3, &mut thread_rng()).unwrap().apply_action(Action::Income(0), &mut thread_rng()).unwrap();
+        assert!(GameRecord::new(&coup).is_err());
+    }
+}
@@ -0,0 +1,422 @@
+// rules conformance cases written down as json, so they can be added without writing any rust
+//
+//   {
+//     "name": "a steal blocked with a real ambassador stands",
+//     "players": 4,
+//     "hands": { "0": ["captain", "duke"], "2": ["ambassador", "duke"] },
+//     "steps": [
+//       { "action": "0>steal:2" },
+//       { "action": "1:pass" },
+//       { "action": "2:block:ambassador", "expect": { "phase": { "AwaitingProposalBlockResponse": 2 } } },
+//       { "action": "0:reveal:0", "rejected": true },
+//       ...
+//       { "action": "0:lose:0", "expect": { "coins": { "0": 2, "2": 2 }, "live_cards": { "0": ["duke"] } } }
+//     ]
+//   }
+//
+// the setup is rules (official, reformation, inquisitor or two-player, official if left out) and
+// players, or a whole config for anything else, then any of hands, revealed, coins, factions, deck,
+// current_player and treasury_reserve - whatever's left out is dealt the way CoupBuilder deals it
+//
+// each step is an action in notation.rs form, or a card drawn at a chance node, and what to expect
+// afterward - "rejected": true expects the action to be refused instead
+// draws that aren't written down come off the top of the deck, so a scenario always plays out the same
+//
+// expect takes any of coins, live_cards, phase (State as serde writes it), current_player,
+// active_player, actions (the exact legal set, in any order), allowed, forbidden, winner,
+// placements, treasury_reserve and deck_size
+// "winner": null expects nobody to have won, still playing or drawn, and placements expects the game
+// to be over with everyone finishing where GameOutcome::placements puts them, like [[0, 2], [1]] for
+// a draw at the turn limit with player 1 knocked out
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use serde::Deserialize;
+use crate::{Action, Character, Coup, CoupBuilder, CoupError, Faction, GameConfig, State};
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    rules: Option<String>,
+    players: Option<u8>,
+    config: Option<GameConfig>,
+    hands: BTreeMap<usize, Vec<String>>,
+    // card indexes that start face up
+    revealed: BTreeMap<usize, Vec<usize>>,
+    coins: BTreeMap<usize, u8>,
+    factions: BTreeMap<usize, String>,
+    // top card first
+    deck: Option<Vec<String>>,
+    current_player: Option<usize>,
+    treasury_reserve: Option<u8>,
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Step {
+    action: Option<String>,
+    draw: Option<String>,
+    rejected: bool,
+    expect: Option<Expect>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Expect {
+    coins: BTreeMap<usize, u8>,
+    live_cards: BTreeMap<usize, Vec<String>>,
+    phase: Option<State>,
+    current_player: Option<usize>,
+    active_player: Option<usize>,
+    actions: Option<Vec<String>>,
+    allowed: Vec<String>,
+    forbidden: Vec<String>,
+    // left out isn't checked, null is nobody
+    #[serde(deserialize_with = "present")]
+    winner: Option<Option<usize>>,
+    placements: Option<Vec<Vec<usize>>>,
+    treasury_reserve: Option<u8>,
+    deck_size: Option<usize>,
+}
+
+// tells a null that's written down apart from a field that's left out
+fn present<'de, D: serde::Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
+impl FromStr for Scenario {
+    type Err = CoupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map_err(|err| CoupError::ScenarioFailed(format!("can't read it: {err}")))
+    }
+}
+
+impl Scenario {
+    // the game as it stands before the first step
+    pub fn start(&self) -> Result<Coup, CoupError> {
+        let config = match (self.config, self.rules.as_deref()) {
+            (Some(_), Some(_)) => return Err(invalid("give either rules or a config, not both")),
+            (Some(config), None) => config,
+            (None, rules) => {
+                let rules = rules.unwrap_or("official");
+                let num_players = match (self.players, rules) {
+                    (Some(num_players), _) => num_players,
+                    (None, "two-player") => 2,
+                    (None, _) => return Err(invalid(&format!("{rules} rules need a number of players"))),
+                };
+                GameConfig::named(rules, num_players).ok_or_else(|| invalid(&format!("\"{rules}\" aren't rules this knows")))?
+            }
+        };
+
+        if self.players.is_some_and(|num_players| num_players != config.num_players) {
+            return Err(invalid("players doesn't match the config"));
+        }
+
+        let mut builder = CoupBuilder::with_config(config);
+
+        for (&player_idx, hand) in &self.hands {
+            let revealed = self.revealed.get(&player_idx).map(Vec::as_slice).unwrap_or_default();
+            let cards = hand.iter()
+                .enumerate()
+                .map(|(card_idx, character)| Ok((character.parse()?, revealed.contains(&card_idx))))
+                .collect::<Result<Vec<(Character, bool)>, CoupError>>()?;
+            builder = builder.cards(player_idx, &cards);
+        }
+
+        if let Some(player_idx) = self.revealed.keys().find(|player_idx| !self.hands.contains_key(player_idx)) {
+            return Err(invalid(&format!("player {player_idx} has revealed cards but no hand")));
+        }
+
+        for (&player_idx, &coins) in &self.coins {
+            builder = builder.coins(player_idx, coins);
+        }
+
+        for (&player_idx, faction) in &self.factions {
+            builder = builder.faction(player_idx, parse_faction(faction)?);
+        }
+
+        if let Some(deck) = &self.deck {
+            builder = builder.deck(&parse_all::<Character>(deck)?);
+        }
+
+        if let Some(player_idx) = self.current_player {
+            builder = builder.current_player(player_idx);
+        }
+
+        if let Some(treasury_reserve) = self.treasury_reserve {
+            builder = builder.treasury_reserve(treasury_reserve);
+        }
+
+        builder.build()
+    }
+
+    // plays every step, checking what each one expects, and gives back where the game ended up
+    pub fn run(&self) -> Result<Coup, CoupError> {
+        let mut game = self.start()?;
+
+        for (step_idx, step) in self.steps.iter().enumerate() {
+            let fail = |reason: String| {
+                let what = step.action.as_deref().or(step.draw.as_deref()).unwrap_or("no action");
+                CoupError::ScenarioFailed(format!("{}: step {} ({what}) {reason}", self.name, step_idx + 1))
+            };
+
+            match (&step.action, &step.draw) {
+                (Some(action), None) => {
+                    let action: Action = action.parse()?;
+                    match (game.apply_deterministic(action), step.rejected) {
+                        (Ok(next), false) => game = next,
+                        (Err(err), false) => return Err(fail(format!("was rejected: {err}"))),
+                        (Ok(_), true) => return Err(fail("was allowed".to_string())),
+                        (Err(_), true) => {}
+                    }
+                }
+                (None, Some(character)) => {
+                    game = game.apply_chance(character.parse()?).map_err(|err| fail(format!("couldn't be drawn: {err}")))?;
+                }
+                (Some(_), Some(_)) => return Err(fail("is both an action and a draw".to_string())),
+                (None, None) => {}
+            }
+
+            // draws aren't left for the next step unless it says which card comes up
+            let next_draws = self.steps.get(step_idx + 1).is_some_and(|next| next.draw.is_some());
+            while game.is_chance_node() && !next_draws {
+                game = game.apply_chance(game.deck[0])?;
+            }
+
+            if let Some(expect) = &step.expect {
+                expect.check(&game).map_err(fail)?;
+            }
+        }
+
+        Ok(game)
+    }
+}
+
+impl Expect {
+    // every way the game differs from what was expected, or nothing
+    fn check(&self, game: &Coup) -> Result<(), String> {
+        let mut problems = Vec::new();
+        let player = |player_idx: usize| match player_idx < game.num_players() {
+            true => Ok(game.player(player_idx)),
+            false => Err(format!("there's no player {player_idx}")),
+        };
+
+        for (&player_idx, &coins) in &self.coins {
+            match player(player_idx) {
+                Ok(player) if player.money() != coins => problems.push(format!("player {player_idx} has ${} instead of ${coins}", player.money())),
+                Ok(_) => {}
+                Err(problem) => problems.push(problem),
+            }
+        }
+
+        for (&player_idx, live_cards) in &self.live_cards {
+            let expected = parse_all::<Character>(live_cards).map_err(|err| err.to_string())?;
+            match player(player_idx) {
+                Ok(player) => {
+                    let live: Vec<Character> = player.influence_cards().iter().filter(|card| !card.1).map(|card| card.0).collect();
+                    if live != expected {
+                        problems.push(format!("player {player_idx} has {} instead of {}", list(&live), list(&expected)));
+                    }
+                }
+                Err(problem) => problems.push(problem),
+            }
+        }
+
+        if let Some(phase) = self.phase {
+            if *game.state() != phase {
+                problems.push(format!("the phase is {:?} instead of {phase:?}", game.state()));
+            }
+        }
+
+        if let Some(player_idx) = self.current_player {
+            if game.current_player_idx() != player_idx {
+                problems.push(format!("it's player {}'s turn instead of player {player_idx}'s", game.current_player_idx()));
+            }
+        }
+
+        if let Some(player_idx) = self.active_player {
+            if game.active_player_idx() != player_idx {
+                problems.push(format!("player {} is acting instead of player {player_idx}", game.active_player_idx()));
+            }
+        }
+
+        let legal = game.actions();
+
+        if let Some(actions) = &self.actions {
+            let expected = parse_all::<Action>(actions).map_err(|err| err.to_string())?;
+            let missing: Vec<Action> = expected.iter().filter(|action| !legal.contains(action)).copied().collect();
+            let extra: Vec<Action> = legal.iter().filter(|action| !expected.contains(action)).copied().collect();
+            if !missing.is_empty() {
+                problems.push(format!("{} can't be played", list(&missing)));
+            }
+            if !extra.is_empty() {
+                problems.push(format!("{} can be played as well", list(&extra)));
+            }
+        }
+
+        for action in parse_all::<Action>(&self.allowed).map_err(|err| err.to_string())? {
+            if !legal.contains(&action) {
+                problems.push(format!("{action} can't be played"));
+            }
+        }
+
+        for action in parse_all::<Action>(&self.forbidden).map_err(|err| err.to_string())? {
+            if legal.contains(&action) {
+                problems.push(format!("{action} can be played"));
+            }
+        }
+
+        if let Some(winner) = self.winner {
+            match (game.winner(), winner) {
+                (actual, expected) if actual == expected => {}
+                (Some(actual), _) => problems.push(format!("player {actual} won instead")),
+                (None, _) => problems.push("nobody has won".to_string()),
+            }
+        }
+
+        if let Some(placements) = &self.placements {
+            match game.outcome() {
+                Some(outcome) if outcome.placements() != *placements => problems.push(format!("the placements are {:?} instead", outcome.placements())),
+                Some(_) => {}
+                None => problems.push("the game isn't over".to_string()),
+            }
+        }
+
+        if let Some(treasury_reserve) = self.treasury_reserve {
+            if game.treasury_reserve() != treasury_reserve {
+                problems.push(format!("the treasury reserve has ${} instead of ${treasury_reserve}", game.treasury_reserve()));
+            }
+        }
+
+        if let Some(deck_size) = self.deck_size {
+            if game.deck_size() != deck_size {
+                problems.push(format!("the deck has {} cards instead of {deck_size}", game.deck_size()));
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(problems.join(", ")),
+        }
+    }
+}
+
+// runs every .json file in a directory as a scenario, giving how many passed or every one that didn't
+pub fn run_scenarios(dir: impl AsRef<Path>) -> Result<usize, Vec<(PathBuf, CoupError)>> {
+    let dir = dir.as_ref();
+    let entries = fs::read_dir(dir).map_err(|err| vec![(dir.to_path_buf(), CoupError::ScenarioFailed(format!("can't read the directory: {err}")))])?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .collect();
+    paths.sort();
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let result = fs::read_to_string(path)
+            .map_err(|err| CoupError::ScenarioFailed(format!("can't read it: {err}")))
+            .and_then(|json| json.parse::<Scenario>())
+            .and_then(|scenario| scenario.run());
+
+        if let Err(err) = result {
+            failures.push((path.clone(), err));
+        }
+    }
+
+    match failures.is_empty() {
+        true => Ok(paths.len()),
+        false => Err(failures),
+    }
+}
+
+fn invalid(reason: &str) -> CoupError {
+    CoupError::ScenarioFailed(format!("bad setup: {reason}"))
+}
+
+fn parse_all<T: FromStr<Err=CoupError>>(strings: &[String]) -> Result<Vec<T>, CoupError> {
+    strings.iter().map(|s| s.parse()).collect()
+}
+
+fn parse_faction(s: &str) -> Result<Faction, CoupError> {
+    match s.trim().to_lowercase().as_str() {
+        "loyalist" => Ok(Faction::Loyalist),
+        "reformist" => Ok(Faction::Reformist),
+        _ => Err(invalid(&format!("\"{s}\" isn't a faction"))),
+    }
+}
+
+fn list<T: ToString>(items: &[T]) -> String {
+    match items.is_empty() {
+        true => "nothing".to_string(),
+        false => items.iter().map(T::to_string).collect::<Vec<_>>().join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CoupError;
+    use crate::scenario::{Scenario, run_scenarios};
+
+    #[test]
+    fn runs_scenario_files() {
+        match run_scenarios(concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios")) {
+            Ok(num_scenarios) => assert!(num_scenarios >= 8),
+            Err(failures) => panic!("{}", failures.iter().map(|(path, err)| format!("{}: {err}", path.display())).collect::<Vec<_>>().join("\n")),
+        }
+    }
+
+    #[test]
+    fn reports_what_went_wrong() {
+        let scenario: Scenario = r#"{
+            "name": "income",
+            "players": 3,
+            "steps": [
+                { "action": "0:income", "expect": { "coins": { "0": 4 }, "current_player": 1, "allowed": ["1:tax"] } },
+                { "action": "1:coup:0" }
+            ]
+        }"#.parse().unwrap();
+
+        let err = scenario.run().unwrap_err();
+        assert_eq!(err, CoupError::ScenarioFailed("income: step 1 (0:income) player 0 has $3 instead of $4".to_string()));
+
+        let scenario: Scenario = r#"{
+            "name": "coup without the coins",
+            "players": 3,
+            "steps": [
+                { "action": "0:income" },
+                { "action": "1>coup:0" }
+            ]
+        }"#.parse().unwrap();
+
+        let err = scenario.run().unwrap_err().to_string();
+        assert!(err.starts_with("scenario failed: coup without the coins: step 2 (1>coup:0) was rejected"), "{err}");
+
+        let scenario: Scenario = r#"{
+            "name": "nobody has won yet",
+            "players": 2,
+            "rules": "two-player",
+            "steps": [
+                { "action": "0:income", "expect": { "winner": null } },
+                { "action": "1:income", "expect": { "winner": 1, "placements": [[1], [0]] } }
+            ]
+        }"#.parse().unwrap();
+
+        let err = scenario.run().unwrap_err();
+        assert_eq!(err, CoupError::ScenarioFailed("nobody has won yet: step 2 (1:income) nobody has won, the game isn't over".to_string()));
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(r#"{ "name": "typo", "player": 3 }"#.parse::<Scenario>().is_err());
+        assert!(r#"{ "name": "no players" }"#.parse::<Scenario>().unwrap().start().is_err());
+        assert!(r#"{ "name": "incomplete config", "config": { "num_players": 3 } }"#.parse::<Scenario>().is_err());
+        assert!(r#"{ "name": "bad hand", "players": 2, "hands": { "0": ["duke", "king"] } }"#.parse::<Scenario>().unwrap().start().is_err());
+        assert!(r#"{ "name": "2 players", "rules": "two-player" }"#.parse::<Scenario>().unwrap().start().is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{Action, Character, Coup, Draw, Faction, State};
+use crate::config::MAX_EXCHANGE_DRAW;
+use crate::inline_vec::InlineVec;
+use crate::known_cards::KnownCards;
+use crate::zobrist::Zobrist;
+
+// a single change made to the game while applying an action in place
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Change {
+    // (player, money before)
+    Money(usize, u8),
+    // (player, faction before)
+    Faction(usize, Option<Faction>),
+    // (player, card index, card before)
+    Card(usize, usize, (Character, bool)),
+    // a card was added to the end of the player's hand
+    CardPushed(usize),
+    // number of cards put on the bottom of the deck
+    DeckPushed(usize),
+    // (deck index, card) taken out of the deck
+    DeckRemoved(usize, Character),
+    ExchangeCardPushed,
+    // the exchange cards from before they were cleared
+    ExchangeCardsCleared(InlineVec<Character, { MAX_EXCHANGE_DRAW as usize }>),
+    // known cards from before one was learned or forgotten
+    KnownCards(KnownCards),
+    DrawPushed,
+    // the draw that was resolved and taken off the front of the pending draws
+    DrawResolved(Draw),
+    EliminationPushed,
+}
+
+// everything needed to take back an action that was applied in place, see Coup::undo()
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Undo {
+    turn: usize,
+    current_player_idx: usize,
+    state: State,
+    priority_player_idx: Option<usize>,
+    proposal: Option<Action>,
+    proposal_blocked_with: Option<Character>,
+    treasury_reserve: u8,
+    history_len: usize,
+    zobrist: Zobrist,
+    changes: Vec<Change>,
+    // changes are only kept when the undo is going to be used
+    tracked: bool,
+    // parts of the position that changed, kept either way so the keys can be brought up to date
+    dirty: u32,
+}
+
+impl Undo {
+    // the turn bookkeeping is cheap to copy, everything else is recorded as it changes
+    pub(crate) fn new(game: &Coup) -> Self {
+        Self {
+            turn: game.turn,
+            current_player_idx: game.current_player_idx,
+            state: game.state,
+            priority_player_idx: game.priority_player_idx,
+            proposal: game.proposal,
+            proposal_blocked_with: game.proposal_blocked_with,
+            treasury_reserve: game.treasury_reserve,
+            history_len: game.history.len(),
+            zobrist: game.zobrist,
+            changes: Vec::new(),
+            tracked: true,
+            dirty: 0,
+        }
+    }
+
+    pub(crate) fn untracked(game: &Coup) -> Self {
+        Self {
+            tracked: false,
+            ..Self::new(game)
+        }
+    }
+
+    pub(crate) fn push(&mut self, change: Change) {
+        self.dirty |= change.part_bit();
+        if self.tracked {
+            self.changes.push(change);
+        }
+    }
+
+    pub(crate) fn dirty(&self) -> u32 {
+        self.dirty
+    }
+}
+
+impl Coup {
+    // takes back an action applied in place - undos have to be applied in the reverse order they were made
+    pub fn undo(&mut self, undo: Undo) {
+        for change in undo.changes.into_iter().rev() {
+            match change {
+                Change::Money(player_idx, money) => self.players[player_idx].money = money,
+                Change::Faction(player_idx, faction) => self.players[player_idx].faction = faction,
+                Change::Card(player_idx, card_idx, card) => self.players[player_idx].influence_cards[card_idx] = card,
+                Change::CardPushed(player_idx) => {
+                    self.players[player_idx].influence_cards.pop();
+                }
+                Change::DeckPushed(num_cards) => self.deck.truncate(self.deck.len() - num_cards),
+                Change::DeckRemoved(deck_idx, card) => self.deck.insert(deck_idx, card),
+                Change::ExchangeCardPushed => {
+                    self.exchange_cards.pop();
+                }
+                Change::ExchangeCardsCleared(exchange_cards) => self.exchange_cards = exchange_cards,
+                Change::KnownCards(known_cards) => self.known_cards = known_cards,
+                Change::DrawPushed => {
+                    self.pending_draws.pop();
+                }
+                Change::DrawResolved(draw) => self.pending_draws.insert(0, draw),
+                Change::EliminationPushed => {
+                    self.eliminations.pop();
+                }
+            }
+        }
+
+        self.turn = undo.turn;
+        self.current_player_idx = undo.current_player_idx;
+        self.state = undo.state;
+        self.priority_player_idx = undo.priority_player_idx;
+        self.proposal = undo.proposal;
+        self.proposal_blocked_with = undo.proposal_blocked_with;
+        self.treasury_reserve = undo.treasury_reserve;
+        self.history.truncate(undo.history_len);
+        self.zobrist = undo.zobrist;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+    use crate::{Action, Coup, CoupBuilder, GameConfig};
+    use crate::Character::{Contessa, Duke};
+
+    #[test]
+    fn undo_restores_exactly() {
+        let mut rng = thread_rng();
+        let configs = [GameConfig::official(4), GameConfig::reformation(4), GameConfig::inquisitor(4), GameConfig::two_player()];
+
+        for config in configs {
+            for _ in 0..20 {
+                let mut coup = Coup::with_config(config, &mut rng).unwrap();
+                let mut snapshots = Vec::new();
+                let mut undos = Vec::new();
+
+                while coup.winner().is_none() && coup.turn < 100 {
+                    let actions = coup.actions();
+                    let action = actions[rng.gen_range(0..actions.len())];
+
+                    snapshots.push(coup.clone());
+                    undos.push(coup.apply_action_mut(action, &mut rng).unwrap());
+                }
+
+                // walk all the way back up, checking every position on the way
+                while let Some(undo) = undos.pop() {
+                    coup.undo(undo);
+
+                    // deck order is part of the comparison, the history has to be checked separately
+                    let snapshot = snapshots.pop().unwrap();
+                    assert_eq!(coup, snapshot);
+                    assert_eq!(coup.history(), snapshot.history());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn undo_chance() {
+        let mut coup = CoupBuilder::new(3).hand(0, Duke, Contessa).build().unwrap();
+        let start = coup.clone();
+
+        let mut undos = Vec::new();
+        for action in [Action::Tax(0), Action::Challenge(1), Action::Reveal(0, 0)] {
+            undos.push(coup.apply_deterministic_mut(action).unwrap());
+        }
+
+        let before_draw = coup.clone();
+        let character = coup.chance_outcomes()[0].0;
+        let undo = coup.apply_chance_mut(character).unwrap();
+        assert!(!coup.is_chance_node());
+
+        coup.undo(undo);
+        assert_eq!(coup, before_draw);
+
+        while let Some(undo) = undos.pop() {
+            coup.undo(undo);
+        }
+
+        assert_eq!(coup, start);
+        assert!(coup.history().is_empty());
+    }
+}
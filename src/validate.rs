@@ -0,0 +1,394 @@
+use crate::{Action, Coup, CoupError, State};
+use crate::chance::Draw;
+use crate::Character::{Ambassador, Assassin, Captain, Contessa, Duke, Inquisitor};
+
+fn invalid(reason: String) -> Result<(), CoupError> {
+    Err(CoupError::InvalidState(reason))
+}
+
+impl Coup {
+    // checks that the game is in a position the rules could actually have got it to, for games
+    // that came from somewhere other than playing actions, like a save file
+    // debug builds check this after every action and draw
+    pub fn validate(&self) -> Result<(), CoupError> {
+        self.config.validate()?;
+
+        if self.players.len() != self.config.num_players as usize {
+            return invalid(format!("the config is for {} players, but there are {}", self.config.num_players, self.players.len()));
+        }
+
+        self.validate_cards()?;
+        self.validate_purses()?;
+        self.validate_eliminations()?;
+        self.validate_turn()?;
+        self.validate_known_cards()
+    }
+
+    // every card in the game is in a hand, the deck or the exchange pool, exactly once
+    fn validate_cards(&self) -> Result<(), CoupError> {
+        let mut counts = [0usize; 6];
+        for player_idx in self.players_indexes() {
+            for (card_idx, &(card, _)) in self.players[player_idx].influence_cards.iter().enumerate() {
+                // a card being replaced is already back in the deck, it only stays in the hand until the draw
+                if !self.pending_draws.contains(&Draw::Influence(player_idx, card_idx)) {
+                    counts[card as usize] += 1;
+                }
+            }
+        }
+
+        for &card in self.deck.iter().chain(&self.exchange_cards) {
+            counts[card as usize] += 1;
+        }
+
+        for character in [Duke, Assassin, Captain, Ambassador, Contessa, Inquisitor] {
+            let expected = match self.config.characters().contains(&character) {
+                true => self.config.copies_per_character as usize,
+                false => 0,
+            };
+
+            if counts[character as usize] != expected {
+                return invalid(format!("there are {} {:?} cards in the game instead of {expected}", counts[character as usize], character));
+            }
+        }
+
+        // hands only ever hold two cards, counting the ones still to be drawn
+        let selecting = match self.state {
+            State::AwaitingInfluenceSelection(selecting_player_idx) => Some(selecting_player_idx),
+            _ => None,
+        };
+
+        for player_idx in self.players_indexes() {
+            let hand_size = self.players[player_idx].influence_cards.len();
+            let expected = match selecting {
+                Some(selecting_player_idx) => usize::from(player_idx < selecting_player_idx),
+                None => 2,
+            };
+            let to_draw = self.pending_draws.iter()
+                .filter(|&&draw| matches!(draw, Draw::Influence(drawing_player_idx, card_idx) if drawing_player_idx == player_idx && card_idx >= hand_size))
+                .count();
+
+            if hand_size + to_draw != expected {
+                return invalid(format!("player {player_idx} has {} cards instead of {expected}", hand_size + to_draw));
+            }
+        }
+
+        for &draw in &self.pending_draws {
+            match draw {
+                Draw::Influence(player_idx, card_idx) if player_idx >= self.players.len() || card_idx >= 2 => {
+                    return invalid(format!("{:?} is for a card that can't exist", draw));
+                }
+                Draw::Influence(player_idx, card_idx) if self.players[player_idx].influence_cards.get(card_idx).is_some_and(|card| card.1) => {
+                    return invalid(format!("{:?} would replace a revealed card", draw));
+                }
+                Draw::Exchange(player_idx) if self.state != State::AwaitingExchangeChoice || player_idx != self.current_player_idx => {
+                    return invalid(format!("{:?} doesn't fit {:?}", draw, self.state));
+                }
+                _ => {}
+            }
+        }
+
+        // the pool is whatever an exchange has drawn so far
+        let exchange_draws = self.pending_draws.iter().filter(|draw| matches!(draw, Draw::Exchange(_))).count();
+        let pool_size = match self.state {
+            State::AwaitingExchangeChoice => self.config.exchange_draw as usize,
+            _ => 0,
+        };
+
+        if self.exchange_cards.len() + exchange_draws != pool_size {
+            return invalid(format!("{:?} has {} exchange cards drawn or to draw instead of {pool_size}", self.state, self.exchange_cards.len() + exchange_draws));
+        }
+
+        Ok(())
+    }
+
+    fn validate_purses(&self) -> Result<(), CoupError> {
+        // coins are unsigned, so the only limits are the coin cap and the treasury reserve belonging to reformation games
+        if !self.config.reformation && self.treasury_reserve > 0 {
+            return invalid(format!("there's ${} in the treasury reserve outside a reformation game", self.treasury_reserve));
+        }
+
+        if let Some(coin_cap) = self.config.coin_cap {
+            if self.treasury_reserve > coin_cap {
+                return invalid(format!("there's ${} in the treasury reserve, over the ${coin_cap} cap", self.treasury_reserve));
+            }
+
+            if let Some(player_idx) = self.players_indexes().find(|&player_idx| self.players[player_idx].money > coin_cap) {
+                return invalid(format!("player {player_idx} has ${}, over the ${coin_cap} cap", self.players[player_idx].money));
+            }
+        }
+
+        for (player_idx, player) in self.players.iter().enumerate() {
+            if player.faction.is_some() != self.config.reformation {
+                return invalid(format!("player {player_idx} has faction {:?} in a game where reformation is {}", player.faction, self.config.reformation));
+            }
+        }
+
+        Ok(())
+    }
+
+    // everyone out of the game is listed once, in the order of the turns they went out on
+    fn validate_eliminations(&self) -> Result<(), CoupError> {
+        if let Some(&(player_idx, _)) = self.eliminations.iter().find(|&&(player_idx, _)| player_idx >= self.players.len()) {
+            return invalid(format!("player {player_idx} is out, but isn't at the table"));
+        }
+
+        // nobody is out while hands are being picked, however few cards they have
+        let selecting = matches!(self.state, State::AwaitingInfluenceSelection(_));
+        for player_idx in self.players_indexes() {
+            let times_out = self.eliminations.iter().filter(|&&(eliminated_idx, _)| eliminated_idx == player_idx).count();
+            let dead = !selecting && self.is_player_dead(player_idx);
+            if times_out != usize::from(dead) {
+                return invalid(format!("player {player_idx} went out {times_out} times, but dead is {dead}"));
+            }
+        }
+
+        let turns = self.eliminations.iter().map(|&(_, turn)| turn);
+        if turns.clone().zip(turns.skip(1)).any(|(turn, next_turn)| next_turn < turn) {
+            return invalid(format!("eliminations {:?} are out of order", self.eliminations));
+        }
+
+        if let Some(&(player_idx, turn)) = self.eliminations.last().filter(|&&(_, turn)| turn > self.turn) {
+            return invalid(format!("player {player_idx} went out on turn {turn}, but it's only turn {}", self.turn));
+        }
+
+        Ok(())
+    }
+
+    fn validate_turn(&self) -> Result<(), CoupError> {
+        let alive = |player_idx: usize, role: &str| match player_idx < self.players.len() {
+            false => invalid(format!("the {role} {player_idx} isn't at the table")),
+            true if self.is_player_dead(player_idx) => invalid(format!("the {role} {player_idx} is out of the game")),
+            true => Ok(()),
+        };
+        let priority = |expected: usize| match self.priority_player_idx {
+            Some(priority_player_idx) if priority_player_idx == expected => Ok(()),
+            priority_player_idx => invalid(format!("{:?} should be decided by player {expected}, not {:?}", self.state, priority_player_idx)),
+        };
+
+        // nobody has any influence to be alive with until it's been picked
+        if let State::AwaitingInfluenceSelection(selecting_player_idx) = self.state {
+            if selecting_player_idx >= self.players.len() {
+                return invalid(format!("player {selecting_player_idx} can't pick influence, they aren't at the table"));
+            }
+
+            if self.proposal.is_some() || self.proposal_blocked_with.is_some() {
+                return invalid("nothing can be proposed while influence is being picked".to_string());
+            }
+
+            return priority(selecting_player_idx);
+        }
+
+        let current_player_idx = self.current_player_idx;
+        alive(current_player_idx, "current player")?;
+
+        match self.state {
+            State::AwaitingProposal => match self.priority_player_idx {
+                Some(priority_player_idx) => invalid(format!("player {priority_player_idx} has priority before anything was proposed")),
+                None => Ok(()),
+            },
+            State::AwaitingProposalResponse(num_remaining_passers) => {
+                let num_responders = self.other_player_indexes(current_player_idx).len();
+                if num_remaining_passers == 0 || num_remaining_passers > num_responders {
+                    return invalid(format!("{num_remaining_passers} players left to respond, but there are {num_responders} who can"));
+                }
+
+                match self.priority_player_idx {
+                    Some(priority_player_idx) if priority_player_idx != current_player_idx => alive(priority_player_idx, "responding player"),
+                    priority_player_idx => invalid(format!("{:?} can't be answered by {:?}", self.state, priority_player_idx)),
+                }
+            }
+            State::AwaitingProposalBlockResponse(blocker_player_idx) => {
+                alive(blocker_player_idx, "blocker")?;
+                if blocker_player_idx == current_player_idx {
+                    return invalid(format!("player {blocker_player_idx} blocked their own proposal"));
+                }
+
+                match self.priority_player_idx {
+                    Some(priority_player_idx) if priority_player_idx != blocker_player_idx => alive(priority_player_idx, "responding player"),
+                    priority_player_idx => invalid(format!("{:?} can't be answered by {:?}", self.state, priority_player_idx)),
+                }
+            }
+            State::AwaitingChallengedBlockResponse(blocker_player_idx, challenger_player_idx) => {
+                alive(blocker_player_idx, "blocker")?;
+                alive(challenger_player_idx, "challenger")?;
+                if blocker_player_idx == challenger_player_idx {
+                    return invalid(format!("player {blocker_player_idx} challenged their own block"));
+                }
+
+                priority(blocker_player_idx)
+            }
+            State::AwaitingChallengedProposalResponse(challenger_player_idx) => {
+                alive(challenger_player_idx, "challenger")?;
+                if challenger_player_idx == current_player_idx {
+                    return invalid(format!("player {challenger_player_idx} challenged their own proposal"));
+                }
+
+                priority(current_player_idx)
+            }
+            State::AwaitingLoseInfluence(player_idx, _) | State::AwaitingExamineShow(player_idx) => {
+                alive(player_idx, "player losing or showing influence")?;
+                priority(player_idx)
+            }
+            State::AwaitingExamineDecision(player_idx, card_idx) => {
+                alive(player_idx, "examined player")?;
+                if card_idx >= self.players[player_idx].influence_cards.len() {
+                    return invalid(format!("player {player_idx} showed card {card_idx}, which they don't have"));
+                }
+
+                priority(current_player_idx)
+            }
+            State::AwaitingExchangeChoice | State::ResolveProposal => priority(current_player_idx),
+            State::AwaitingInfluenceSelection(_) => unreachable!("influence selection was checked above"),
+        }?;
+
+        self.validate_proposal()
+    }
+
+    // the proposal and block are set exactly when the phase of the turn needs them
+    fn validate_proposal(&self) -> Result<(), CoupError> {
+        // a coup isn't a proposal, so losing influence to one is the only phase past the start of a turn without one
+        let needs_proposal = !matches!(self.state, State::AwaitingProposal | State::AwaitingLoseInfluence(_, _));
+        let needs_block = matches!(self.state, State::AwaitingProposalBlockResponse(_) | State::AwaitingChallengedBlockResponse(_, _));
+
+        match self.proposal {
+            Some(proposal) if !proposal.is_proposal() && !matches!(proposal, Action::Coup(_, _)) => {
+                return invalid(format!("{:?} isn't something a player can be in the middle of", proposal));
+            }
+            Some(proposal) if proposal.player_idx() != self.current_player_idx => {
+                return invalid(format!("{:?} was proposed by someone other than the current player {}", proposal, self.current_player_idx));
+            }
+            Some(proposal) if self.state == State::AwaitingProposal => {
+                return invalid(format!("{:?} is still around at the start of a turn", proposal));
+            }
+            None if needs_proposal => return invalid(format!("{:?} needs a proposal", self.state)),
+            _ => {}
+        }
+
+        // a block that stood up to a challenge is still there while the challenger loses influence
+        let may_block = needs_block || matches!(self.state, State::AwaitingLoseInfluence(_, true));
+        if (needs_block && self.proposal_blocked_with.is_none()) || (!may_block && self.proposal_blocked_with.is_some()) {
+            return invalid(format!("{:?} doesn't fit a block with {:?}", self.state, self.proposal_blocked_with));
+        }
+
+        match (self.state, self.proposal) {
+            (State::AwaitingExchangeChoice, Some(Action::Exchange(_))) => Ok(()),
+            (State::AwaitingExamineShow(player_idx) | State::AwaitingExamineDecision(player_idx, _), Some(Action::Examine(_, examined_player_idx)))
+                if player_idx == examined_player_idx => Ok(()),
+            (State::AwaitingExchangeChoice | State::AwaitingExamineShow(_) | State::AwaitingExamineDecision(_, _), proposal) => {
+                invalid(format!("{:?} can't come from {:?}", self.state, proposal))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // known cards always point at a card someone else has
+    fn validate_known_cards(&self) -> Result<(), CoupError> {
+        for (observer_player_idx, owner_player_idx, card_idx) in self.known_cards.iter() {
+            let in_hand = owner_player_idx < self.players.len() && card_idx < self.players[owner_player_idx].influence_cards.len();
+            if observer_player_idx >= self.players.len() || observer_player_idx == owner_player_idx || !in_hand {
+                return invalid(format!("player {observer_player_idx} can't know card {card_idx} of player {owner_player_idx}"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::thread_rng;
+    use crate::{Action, Coup, CoupBuilder, CoupError, GameConfig, State};
+    use crate::Character::{Captain, Contessa, Duke};
+
+    fn assert_invalid(coup: &Coup) {
+        assert!(matches!(coup.validate(), Err(CoupError::InvalidState(_))), "{:?}", coup.validate());
+    }
+
+    #[test]
+    fn played_games_are_valid() {
+        let mut rng = thread_rng();
+        let configs = [GameConfig::official(4), GameConfig::reformation(4), GameConfig::inquisitor(4), GameConfig::two_player()];
+
+        for config in configs {
+            for _ in 0..10 {
+                let mut coup = Coup::with_config(config, &mut rng).unwrap();
+                assert_eq!(coup.validate(), Ok(()));
+
+                // chance nodes included
+                while coup.winner().is_none() && coup.turn() < 100 {
+                    match coup.pending_draw() {
+                        Some(_) => coup = coup.apply_chance(coup.chance_outcomes()[0].0).unwrap(),
+                        None => coup = coup.apply_deterministic(coup.random_action(&mut rng).unwrap()).unwrap(),
+                    }
+
+                    assert_eq!(coup.validate(), Ok(()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn catches_broken_states() {
+        let coup = CoupBuilder::new(3).hand(0, Duke, Captain).hand(1, Contessa, Contessa).build().unwrap();
+        assert_eq!(coup.validate(), Ok(()));
+
+        // a card that came out of nowhere
+        let mut broken = coup.clone();
+        broken.players[1].influence_cards[0] = (Duke, false);
+        assert_invalid(&broken);
+
+        let mut broken = coup.clone();
+        broken.deck.push(Duke);
+        assert_invalid(&broken);
+
+        let mut broken = coup.clone();
+        broken.treasury_reserve = 2;
+        assert_invalid(&broken);
+
+        let mut broken = coup.clone();
+        broken.players[0].influence_cards[0].1 = true;
+        broken.players[0].influence_cards[1].1 = true;
+        assert_invalid(&broken);
+
+        let mut broken = coup.clone();
+        broken.priority_player_idx = Some(1);
+        assert_invalid(&broken);
+
+        // p1 blocking can't also be the one answering the block
+        let stealing = coup.apply_deterministic(Action::Steal(0, 1)).unwrap();
+        let mut blocked = stealing.apply_deterministic(Action::Block(1, Captain)).unwrap();
+        assert_eq!(blocked.validate(), Ok(()));
+        blocked.priority_player_idx = Some(1);
+        assert_invalid(&blocked);
+
+        let mut broken = stealing.clone();
+        broken.proposal = None;
+        assert_invalid(&broken);
+
+        let mut broken = stealing.clone();
+        broken.proposal_blocked_with = Some(Contessa);
+        assert_invalid(&broken);
+
+        let mut broken = stealing.clone();
+        broken.state = State::AwaitingExchangeChoice;
+        broken.priority_player_idx = Some(0);
+        assert_invalid(&broken);
+    }
+
+    #[test]
+    fn validates_deserialized_states() {
+        let mut rng = thread_rng();
+        let coup = CoupBuilder::new(3).hand(0, Duke, Captain).build().unwrap();
+        let coup = coup.apply_action(Action::Tax(0), &mut rng).unwrap();
+
+        let json = serde_json::to_value(&coup).unwrap();
+        let loaded: Coup = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(loaded.validate(), Ok(()));
+
+        // a save that was edited to have the tax already paid, but forgot to end the turn
+        let mut edited = json;
+        edited["state"] = serde_json::json!("AwaitingProposal");
+        let loaded: Coup = serde_json::from_value(edited).unwrap();
+        assert_invalid(&loaded);
+    }
+}
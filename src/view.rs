@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use crate::{Action, CardView, Character, Coup, Departure, Draw, Event, Faction, GameConfig, Player, State};
+use crate::history::History;
+use crate::inline_vec::InlineVec;
+use crate::known_cards::KnownCards;
+use crate::zobrist::Zobrist;
+
+// what everyone can see about a player, plus their cards as the viewer sees them
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerSummary {
+    pub coins: u8,
+    pub faction: Option<Faction>,
+    pub cards: Vec<CardView>,
+}
+
+// everything a single player legally knows about the game, safe to send to that player
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerView {
+    pub player_idx: usize,
+    pub config: GameConfig,
+    pub turn: usize,
+    pub current_player_idx: usize,
+    pub priority_player_idx: Option<usize>,
+    pub state: State,
+    pub proposal: Option<Action>,
+    pub proposal_blocked_with: Option<Character>,
+    pub players: Vec<PlayerSummary>,
+    pub deck_size: usize,
+    pub treasury_reserve: u8,
+    // (player, turn) for everyone who's out, in the order they went
+    pub eliminations: Vec<(usize, usize)>,
+    // cards drawn for an exchange, only filled in for the player doing the exchange
+    pub exchange_cards: Vec<Character>,
+    // draws everyone is waiting on before anyone can act - which card comes up isn't known until it's drawn
+    pub pending_draws: Vec<Draw>,
+    // public events plus the ones private to this player
+    pub history: Vec<Event>,
+}
+
+impl Coup {
+    pub fn view(&self, player_idx: usize) -> PlayerView {
+        let exchange_cards = if self.current_player_idx == player_idx {
+            self.exchange_cards.to_vec()
+        } else {
+            Vec::new()
+        };
+
+        PlayerView {
+            player_idx,
+            config: self.config,
+            turn: self.turn,
+            current_player_idx: self.current_player_idx,
+            priority_player_idx: self.priority_player_idx,
+            state: self.state,
+            proposal: self.proposal,
+            proposal_blocked_with: self.proposal_blocked_with,
+            players: self.players_indexes().map(|owner_idx| PlayerSummary {
+                coins: self.players[owner_idx].money,
+                faction: self.players[owner_idx].faction,
+                cards: self.cards_visible_to(player_idx, owner_idx),
+            }).collect(),
+            deck_size: self.deck.len(),
+            treasury_reserve: self.treasury_reserve,
+            eliminations: self.eliminations.to_vec(),
+            exchange_cards,
+            pending_draws: self.pending_draws.to_vec(),
+            history: self.history_visible_to(player_idx).cloned().collect(),
+        }
+    }
+}
+
+impl PlayerView {
+    // nobody can act until the pending draws are made, see Coup::is_chance_node()
+    pub fn is_chance_node(&self) -> bool {
+        !self.pending_draws.is_empty()
+    }
+
+    // the same actions Coup::actions() gives this player, so leaving isn't among them - see can_leave()
+    pub fn actions(&self) -> Vec<Action> {
+        self.placeholder_game()
+            .actions()
+            .into_iter()
+            .filter(|action| action.player_idx() == self.player_idx)
+            .collect()
+    }
+
+    // whether this player can leave right now, which is whenever they're still in and have a hand
+    pub fn can_leave(&self) -> bool {
+        self.placeholder_game().is_legal(&Action::Leave(self.player_idx, Departure::Concede))
+    }
+
+    // a game that matches this view, with stand-in cards wherever the player can't see
+    // the viewer's legal actions never depend on hidden cards, so it's fine for generating them
+    fn placeholder_game(&self) -> Coup {
+        let placeholder = self.config.characters()[0];
+
+        let players = self.players.iter().map(|summary| Player {
+            money: summary.coins,
+            faction: summary.faction,
+            influence_cards: summary.cards.iter().map(|card| match *card {
+                CardView::Hidden => (placeholder, false),
+                CardView::Live(character) => (character, false),
+                CardView::Revealed(character) => (character, true),
+            }).collect(),
+        }).collect();
+
+        Coup {
+            config: self.config,
+            turn: self.turn,
+            current_player_idx: self.current_player_idx,
+            deck: std::iter::repeat_n(placeholder, self.deck_size).collect(),
+            players,
+            state: self.state,
+            priority_player_idx: self.priority_player_idx,
+            proposal: self.proposal,
+            proposal_blocked_with: self.proposal_blocked_with,
+            exchange_cards: InlineVec::from_slice(&self.exchange_cards),
+            treasury_reserve: self.treasury_reserve,
+            known_cards: KnownCards::default(),
+            pending_draws: InlineVec::from_slice(&self.pending_draws),
+            eliminations: InlineVec::from_slice(&self.eliminations),
+            history: History::default(),
+            zobrist: Zobrist::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+    use crate::{Action, CardView, Coup, CoupBuilder, Departure, EventKind, GameConfig, Visibility};
+    use crate::Character::{Captain, Contessa, Duke};
+
+    #[test]
+    fn view_hides_opponent_cards() {
+        let mut rng = thread_rng();
+        let coup = CoupBuilder::new(3)
+            .cards(0, &[(Captain, false), (Duke, true)])
+            .hand(1, Duke, Contessa)
+            .build()
+            .unwrap();
+
+        let view = coup.view(1);
+        assert_eq!(view.players[0].cards, vec![CardView::Hidden, CardView::Revealed(Duke)]);
+        assert!(view.players[1].cards.iter().all(|card| matches!(card, CardView::Live(_))));
+        assert_eq!(view.deck_size, 9);
+
+        // nothing about the hidden card makes it into what gets sent out
+        let json = serde_json::to_string(&view).unwrap();
+        assert!(!json.contains("Captain"));
+
+        // including what they've drawn
+        let mut coup = coup.apply_action(Action::Exchange(0), &mut rng).unwrap();
+        coup = coup.apply_action(Action::Pass(1), &mut rng).unwrap();
+        coup = coup.apply_action(Action::Pass(2), &mut rng).unwrap();
+        coup = coup.apply_action(Action::Resolve(0), &mut rng).unwrap();
+        assert_eq!(coup.view(0).history.len(), coup.history().len());
+        assert_eq!(coup.view(1).history.len(), coup.history().len() - 2);
+
+        // the public actions are all there for everyone, the draws only for the one who drew
+        let view = coup.view(1);
+        assert!(view.history.iter().all(|event| event.visibility == Visibility::Public));
+        assert!(view.history.iter().any(|event| event.kind == EventKind::Action(Action::Exchange(0))));
+        assert!(!view.history.iter().any(|event| matches!(event.kind, EventKind::CardDrawn(_, _))));
+
+        let view = coup.view(0);
+        assert_eq!(view.players[0].cards, vec![CardView::Live(Captain), CardView::Revealed(Duke)]);
+    }
+
+    #[test]
+    fn view_actions_match_game_actions() {
+        let mut rng = thread_rng();
+        let configs = [GameConfig::official(4), GameConfig::reformation(5), GameConfig::inquisitor(3), GameConfig::two_player()];
+
+        for config in configs {
+            for _ in 0..20 {
+                let mut coup = Coup::with_config(config, &mut rng).unwrap();
+
+                while coup.winner().is_none() && coup.turn < 200 {
+                    let actions = coup.actions();
+
+                    for player_idx in coup.players_indexes() {
+                        let view = coup.view(player_idx);
+                        let expected: Vec<Action> = actions.iter().filter(|a| a.player_idx() == player_idx).cloned().collect();
+                        assert_eq!(view.actions(), expected);
+                        assert_eq!(view.is_chance_node(), coup.is_chance_node());
+                        assert_eq!(view.can_leave(), coup.is_legal(&Action::Leave(player_idx, Departure::Concede)));
+                    }
+
+                    // chance nodes included, where nobody has anything to do
+                    match coup.pending_draw() {
+                        Some(_) => {
+                            let outcomes = coup.chance_outcomes();
+                            coup = coup.apply_chance(outcomes[rng.gen_range(0..outcomes.len())].0).unwrap();
+                        }
+                        None => {
+                            let action = actions[rng.gen_range(0..actions.len())];
+                            coup = coup.apply_deterministic(action).unwrap();
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
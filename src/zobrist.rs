@@ -0,0 +1,267 @@
+use std::hash::{Hash, Hasher};
+use crate::{Character, Coup, Undo};
+use crate::config::MAX_PLAYERS;
+use crate::undo::Change;
+
+// the position is split into parts that each get their own key, and the key of the whole game is
+// all of them xored together - after an action only the parts it touched are keyed again
+// the players come first, one part each
+const DECK_PART: usize = MAX_PLAYERS as usize;
+const EXCHANGE_PART: usize = DECK_PART + 1;
+const KNOWN_CARDS_PART: usize = DECK_PART + 2;
+const DRAWS_PART: usize = DECK_PART + 3;
+const ELIMINATIONS_PART: usize = DECK_PART + 4;
+// whose turn it is and what they're doing, cheap enough to key again after every action
+const TURN_PART: usize = DECK_PART + 5;
+// never changes once the game is set up
+const CONFIG_PART: usize = DECK_PART + 6;
+const NUM_PARTS: usize = DECK_PART + 7;
+
+const NUM_CHARACTERS: usize = 6;
+
+// (full key, public key) for each part
+type PartKeys = [(u64, u64); NUM_PARTS];
+
+// keys kept up to date as actions are applied - a function of the position only, so Coup leaves
+// it out when comparing games, the same way it does the history
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Zobrist {
+    // nothing until the game has been keyed, and the keys are worked out from scratch until then
+    parts: Option<PartKeys>,
+    full: u64,
+    public: u64,
+}
+
+impl Change {
+    // the part of the position this changed
+    fn part(&self) -> usize {
+        match *self {
+            Change::Money(player_idx, _) |
+            Change::Faction(player_idx, _) |
+            Change::Card(player_idx, _, _) |
+            Change::CardPushed(player_idx) => player_idx,
+            Change::DeckPushed(_) | Change::DeckRemoved(_, _) => DECK_PART,
+            Change::ExchangeCardPushed | Change::ExchangeCardsCleared(_) => EXCHANGE_PART,
+            Change::KnownCards(_) => KNOWN_CARDS_PART,
+            Change::DrawPushed | Change::DrawResolved(_) => DRAWS_PART,
+            Change::EliminationPushed => ELIMINATIONS_PART,
+        }
+    }
+
+    pub(crate) fn part_bit(&self) -> u32 {
+        1 << self.part()
+    }
+}
+
+// a multiply and rotate hash like rustc's - parts are keyed after every action, and sip hash is
+// slow enough to show up in simulations
+struct PartHasher(u64);
+
+impl Hasher for PartHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0.rotate_left(5) ^ n).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+
+    // spread the bits out, so that parts xored together don't cancel
+    fn finish(&self) -> u64 {
+        let mut key = self.0;
+        key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        key ^ (key >> 31)
+    }
+}
+
+fn key<T: Hash>(part_idx: usize, contents: T) -> u64 {
+    let mut hasher = PartHasher(part_idx as u64);
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn character_counts<'a>(cards: impl Iterator<Item=&'a Character>) -> [u8; NUM_CHARACTERS] {
+    let mut counts = [0; NUM_CHARACTERS];
+    for &card in cards {
+        counts[card as usize] += 1;
+    }
+    counts
+}
+
+impl Coup {
+    // a key for the whole position, hidden cards included - equal games always have equal keys
+    // the order of the deck and of the exchange cards doesn't count, since nobody can act on it
+    pub fn zobrist_key(&self) -> u64 {
+        match self.zobrist.parts {
+            Some(_) => self.zobrist.full,
+            None => self.part_keys().iter().fold(0, |key, part| key ^ part.0),
+        }
+    }
+
+    // a key for what everyone at the table can see - games that only differ in hidden cards share it
+    pub fn public_key(&self) -> u64 {
+        match self.zobrist.parts {
+            Some(_) => self.zobrist.public,
+            None => self.part_keys().iter().fold(0, |key, part| key ^ part.1),
+        }
+    }
+
+    // keys the game from scratch, after it's been changed other than by applying actions
+    pub(crate) fn rehash(&mut self) {
+        let parts = self.part_keys();
+
+        self.zobrist = Zobrist {
+            parts: Some(parts),
+            full: parts.iter().fold(0, |key, part| key ^ part.0),
+            public: parts.iter().fold(0, |key, part| key ^ part.1),
+        };
+    }
+
+    // keys again whatever the undo saw change
+    pub(crate) fn update_keys(&mut self, undo: &Undo) {
+        let Some(mut parts) = self.zobrist.parts else {
+            return;
+        };
+
+        let mut dirty = undo.dirty() | 1 << TURN_PART;
+        while dirty != 0 {
+            let part_idx = dirty.trailing_zeros() as usize;
+            let part = self.part_key(part_idx);
+            self.zobrist.full ^= parts[part_idx].0 ^ part.0;
+            self.zobrist.public ^= parts[part_idx].1 ^ part.1;
+            parts[part_idx] = part;
+            dirty &= dirty - 1;
+        }
+
+        self.zobrist.parts = Some(parts);
+    }
+
+    fn part_keys(&self) -> PartKeys {
+        std::array::from_fn(|part_idx| self.part_key(part_idx))
+    }
+
+    fn part_key(&self, part_idx: usize) -> (u64, u64) {
+        match part_idx {
+            DECK_PART => (key(part_idx, character_counts(self.deck.iter())), key(part_idx, self.deck.len())),
+            EXCHANGE_PART => (key(part_idx, character_counts(self.exchange_cards.iter())), key(part_idx, self.exchange_cards.len())),
+            KNOWN_CARDS_PART => {
+                let known_cards = key(part_idx, self.known_cards);
+                (known_cards, known_cards)
+            }
+            DRAWS_PART => {
+                let draws = key(part_idx, self.pending_draws);
+                (draws, draws)
+            }
+            ELIMINATIONS_PART => {
+                let eliminations = key(part_idx, self.eliminations);
+                (eliminations, eliminations)
+            }
+            TURN_PART => {
+                let turn = key(part_idx, (
+                    self.turn,
+                    self.current_player_idx,
+                    self.state,
+                    self.priority_player_idx,
+                    self.proposal,
+                    self.proposal_blocked_with,
+                    self.treasury_reserve,
+                ));
+                (turn, turn)
+            }
+            CONFIG_PART => {
+                let config = key(part_idx, self.config);
+                (config, config)
+            }
+            player_idx if player_idx < self.players.len() => {
+                let player = &self.players[player_idx];
+                let mut public_cards = [None; 2];
+                for (card_idx, &(character, revealed)) in player.influence_cards.iter().enumerate() {
+                    public_cards[card_idx] = Some(revealed.then_some(character));
+                }
+
+                (
+                    key(part_idx, player),
+                    key(part_idx, (player.money, player.faction, public_cards)),
+                )
+            }
+            // seats nobody is sitting in
+            _ => (0, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use rand::thread_rng;
+    use crate::{Action, Coup, CoupBuilder, GameConfig};
+    use crate::Character::{Captain, Contessa, Duke};
+
+    #[test]
+    fn keys_match_a_fresh_hash() {
+        let mut rng = thread_rng();
+        let configs = [GameConfig::official(4), GameConfig::reformation(4), GameConfig::inquisitor(4), GameConfig::two_player()];
+
+        for config in configs {
+            for _ in 0..20 {
+                let mut coup = Coup::with_config(config, &mut rng).unwrap();
+                let mut undos = Vec::new();
+                let mut keys = Vec::new();
+
+                while coup.winner().is_none() && coup.turn < 100 {
+                    keys.push((coup.zobrist_key(), coup.public_key()));
+
+                    let action = coup.random_action(&mut rng).unwrap();
+                    undos.push(coup.apply_action_mut(action, &mut rng).unwrap());
+
+                    let mut fresh = coup.clone();
+                    fresh.rehash();
+                    assert_eq!(coup.zobrist_key(), fresh.zobrist_key());
+                    assert_eq!(coup.public_key(), fresh.public_key());
+                }
+
+                while let Some(undo) = undos.pop() {
+                    coup.undo(undo);
+                    assert_eq!((coup.zobrist_key(), coup.public_key()), keys.pop().unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn public_key_ignores_hidden_cards() {
+        let mut rng = thread_rng();
+        let coup = CoupBuilder::new(3).hand(1, Duke, Duke).build().unwrap();
+
+        let mut other = coup.clone();
+        other.players[1].influence_cards[0] = (Captain, false);
+        other.rehash();
+
+        assert_ne!(coup.zobrist_key(), other.zobrist_key());
+        assert_eq!(coup.public_key(), other.public_key());
+
+        // unless they've been revealed
+        other.players[1].influence_cards[0] = (Contessa, true);
+        other.rehash();
+        assert_ne!(coup.public_key(), other.public_key());
+
+        // a game that was never keyed hashes the same as one that was kept up to date
+        let mut seen = HashSet::new();
+        let income_first = coup.apply_action(Action::Income(0), &mut rng).unwrap();
+        seen.insert(income_first.apply_action(Action::Income(1), &mut rng).unwrap());
+        let mut unkeyed = income_first.apply_action(Action::Income(1), &mut rng).unwrap();
+        unkeyed.zobrist = Default::default();
+        assert!(seen.contains(&unkeyed));
+    }
+}